@@ -0,0 +1,9 @@
+class DemoStudio extends HTMLElement {
+	connectedCallback() {
+		this.innerHTML = '<p style="padding: 1rem">Mock demo plugin</p>';
+	}
+}
+
+if (!customElements.get('demo-studio')) {
+	customElements.define('demo-studio', DemoStudio);
+}
@@ -1,19 +1,64 @@
 use notify::{Event, EventKind, RecursiveMode, Watcher};
-use std::path::PathBuf;
-use std::sync::mpsc;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, OnceLock};
 use std::time::{Duration, Instant};
 use tauri::Emitter;
 
+use crate::daemon_profiles;
+use crate::event_map;
+use crate::proxy_policy;
+
 const CONFIG_FILE: &str = "sidebar.yaml";
 const DEBOUNCE: Duration = Duration::from_millis(500);
 const RECHECK_INTERVAL: Duration = Duration::from_secs(60);
 
-fn config_dir() -> PathBuf {
-    if let Ok(home) = std::env::var("HOME") {
-        PathBuf::from(home).join(".hecate").join("config")
-    } else {
-        PathBuf::from("/run/hecate/config")
+/// A hecate tree set in-process (by `mock_daemon`, or by tests), ahead of
+/// `$HOME`.
+static HOME_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Use `home` as the hecate tree for the rest of the process. Must be called
+/// before anything resolves a path; later calls are ignored.
+#[cfg(debug_assertions)]
+pub fn override_hecate_home(home: PathBuf) {
+    let _ = HOME_OVERRIDE.set(home);
+}
+
+/// A scratch hecate tree shared by this test process, set as the override.
+/// Tests that touch files under the hecate tree all use this one.
+#[cfg(test)]
+pub fn test_home() -> &'static Path {
+    HOME_OVERRIDE.get_or_init(|| {
+        let home = std::env::temp_dir().join(format!("hecate-web-test-{}", std::process::id()));
+        let _ = std::fs::create_dir_all(&home);
+        home
+    })
+}
+
+/// The hecate tree set with [`override_hecate_home`], if any.
+pub fn hecate_home_override() -> Option<&'static Path> {
+    HOME_OVERRIDE.get().map(PathBuf::as_path)
+}
+
+/// Root of the per-user hecate tree: the in-process override, else
+/// `$HOME/.hecate`. `None` when neither is available.
+pub fn hecate_home() -> Option<PathBuf> {
+    if let Some(home) = hecate_home_override() {
+        return Some(home.to_path_buf());
     }
+    std::env::var("HOME").ok().map(|home| PathBuf::from(home).join(".hecate"))
+}
+
+pub fn config_dir() -> PathBuf {
+    hecate_home()
+        .map(|home| home.join("config"))
+        .unwrap_or_else(|| PathBuf::from("/run/hecate/config"))
+}
+
+/// Where hecate-web keeps its own persistent state (caches, queues).
+pub fn state_dir() -> PathBuf {
+    hecate_home()
+        .map(|home| home.join("hecate-web"))
+        .unwrap_or_else(|| PathBuf::from("/run/hecate/hecate-web"))
 }
 
 pub fn start(app: tauri::AppHandle) {
@@ -48,6 +93,22 @@ pub fn start(app: tauri::AppHandle) {
         loop {
             match rx.recv_timeout(RECHECK_INTERVAL) {
                 Ok(event) => {
+                    if touches(&event, proxy_policy::POLICY_FILE) {
+                        eprintln!("[config-watcher] {} changed, reloading", proxy_policy::POLICY_FILE);
+                        proxy_policy::reload();
+                        continue;
+                    }
+                    if touches(&event, event_map::EVENT_MAP_FILE) {
+                        eprintln!("[config-watcher] {} changed, reloading", event_map::EVENT_MAP_FILE);
+                        event_map::reload();
+                        continue;
+                    }
+                    if touches(&event, daemon_profiles::PROFILES_FILE) {
+                        eprintln!("[config-watcher] {} changed, reloading", daemon_profiles::PROFILES_FILE);
+                        daemon_profiles::reload(&app);
+                        continue;
+                    }
+
                     let dominated = event
                         .paths
                         .iter()
@@ -84,3 +145,12 @@ pub fn start(app: tauri::AppHandle) {
         }
     });
 }
+
+/// Whether `event` changed (not just read) the config file `name`.
+fn touches(event: &Event, name: &str) -> bool {
+    !matches!(event.kind, EventKind::Access(_))
+        && event
+            .paths
+            .iter()
+            .any(|p| p.file_name().map(|n| n == name).unwrap_or(false))
+}
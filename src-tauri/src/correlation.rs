@@ -0,0 +1,194 @@
+//! Request correlation ids: `X-Request-Id` and W3C `traceparent`.
+//!
+//! Every request hecate-web sends to the daemon or a plugin daemon carries
+//! both headers. Ids the webview set on a `hecate://` request are kept;
+//! otherwise they are generated here, with the request id defaulting to the
+//! trace id so one value can be grepped for across the web, daemon and
+//! plugin logs. The proxy echoes both back on the response to the webview
+//! and prefixes its log lines with the request id.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tauri::http::{HeaderMap, HeaderValue};
+
+pub const REQUEST_ID: &str = "x-request-id";
+pub const TRACEPARENT: &str = "traceparent";
+/// Longest `X-Request-Id` taken from the webview.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// The correlation ids of one request.
+#[derive(Clone, Debug)]
+pub struct Ids {
+    pub request_id: String,
+    pub traceparent: String,
+}
+
+impl Ids {
+    /// Fresh ids for a request hecate-web starts itself.
+    pub fn generate() -> Self {
+        let trace_id = random_hex(32);
+        Ids {
+            traceparent: format!("00-{}-{}-01", trace_id, random_hex(16)),
+            request_id: trace_id,
+        }
+    }
+
+    /// Set both headers on an outgoing request or response.
+    pub fn apply(&self, headers: &mut HeaderMap) {
+        if let Ok(value) = HeaderValue::from_str(&self.request_id) {
+            headers.insert(REQUEST_ID, value);
+        }
+        if let Ok(value) = HeaderValue::from_str(&self.traceparent) {
+            headers.insert(TRACEPARENT, value);
+        }
+    }
+}
+
+/// Take the ids from `headers`, generating whichever is missing or invalid,
+/// and write the result back so the request carries both.
+pub fn ensure(headers: &mut HeaderMap) -> Ids {
+    let request_id = headers
+        .get(REQUEST_ID)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|id| valid_request_id(id))
+        .map(str::to_string);
+    let traceparent = headers
+        .get(TRACEPARENT)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|tp| valid_traceparent(tp))
+        .map(str::to_string);
+
+    let ids = match (request_id, traceparent) {
+        (Some(request_id), Some(traceparent)) => Ids { request_id, traceparent },
+        (None, Some(traceparent)) => Ids {
+            request_id: traceparent[3..35].to_string(),
+            traceparent,
+        },
+        (Some(request_id), None) => Ids {
+            request_id,
+            traceparent: Ids::generate().traceparent,
+        },
+        (None, None) => Ids::generate(),
+    };
+    ids.apply(headers);
+    ids
+}
+
+fn valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id.bytes().all(|b| b.is_ascii_alphanumeric() || b"-_.:".contains(&b))
+}
+
+/// `{version}-{trace-id}-{parent-id}-{flags}`, lowercase hex, with the
+/// all-zero ids and version `ff` forbidden (W3C Trace Context §3.2).
+/// Versions after `00` may append more `-`-separated fields, which are
+/// ignored; `00` must end after the flags.
+fn valid_traceparent(tp: &str) -> bool {
+    let parts: Vec<&str> = tp.split('-').collect();
+    let [version, trace_id, parent_id, flags, rest @ ..] = parts.as_slice() else {
+        return false;
+    };
+    if *version == "00" && !rest.is_empty() {
+        return false;
+    }
+    let hex = |s: &str, len: usize| s.len() == len && s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'));
+    let nonzero = |s: &str| s.bytes().any(|b| b != b'0');
+    hex(version, 2)
+        && *version != "ff"
+        && hex(trace_id, 32)
+        && nonzero(trace_id)
+        && hex(parent_id, 16)
+        && nonzero(parent_id)
+        && hex(flags, 2)
+}
+
+/// `len` random lowercase hex digits (never all zero).
+fn random_hex(len: usize) -> String {
+    let mut out = String::with_capacity(len);
+    while out.len() < len {
+        out.push_str(&format!("{:016x}", random_u64() | 1));
+    }
+    out.truncate(len);
+    out
+}
+
+/// A random number, unique per call. Good for ids and jitter, not for
+/// secrets: the std hasher's per-process random keys plus a counter.
+pub fn random_u64() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    hasher.write_u128(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or(0),
+    );
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+    const PARENT_ID: &str = "00f067aa0ba902b7";
+
+    #[test]
+    fn traceparent_validation() {
+        let valid = format!("00-{}-{}-01", TRACE_ID, PARENT_ID);
+        assert!(valid_traceparent(&valid));
+        assert!(valid_traceparent(&format!("00-{}-{}-00", TRACE_ID, PARENT_ID)));
+        // Later versions, with and without trailing fields
+        assert!(valid_traceparent(&format!("01-{}-{}-01", TRACE_ID, PARENT_ID)));
+        assert!(valid_traceparent(&format!("01-{}-{}-01-extra", TRACE_ID, PARENT_ID)));
+        assert!(valid_traceparent(&format!("cc-{}-{}-01-what-the-future-holds", TRACE_ID, PARENT_ID)));
+
+        let invalid = [
+            // All-zero ids
+            format!("00-{}-{}-01", "0".repeat(32), PARENT_ID),
+            format!("00-{}-{}-01", TRACE_ID, "0".repeat(16)),
+            // Forbidden version, also with trailing fields
+            format!("ff-{}-{}-01", TRACE_ID, PARENT_ID),
+            format!("ff-{}-{}-01-extra", TRACE_ID, PARENT_ID),
+            // Wrong field lengths
+            format!("0-{}-{}-01", TRACE_ID, PARENT_ID),
+            format!("000-{}-{}-01", TRACE_ID, PARENT_ID),
+            format!("00-{}-{}-01", &TRACE_ID[1..], PARENT_ID),
+            format!("00-{}0-{}-01", TRACE_ID, PARENT_ID),
+            format!("00-{}-{}-01", TRACE_ID, &PARENT_ID[1..]),
+            format!("00-{}-{}0-01", TRACE_ID, PARENT_ID),
+            format!("00-{}-{}-1", TRACE_ID, PARENT_ID),
+            format!("00-{}-{}-001", TRACE_ID, PARENT_ID),
+            // Wrong number of fields; only versions after 00 may add more
+            format!("00-{}-{}", TRACE_ID, PARENT_ID),
+            format!("01-{}-{}", TRACE_ID, PARENT_ID),
+            format!("00-{}-{}-01-extra", TRACE_ID, PARENT_ID),
+            format!("00-{}-{}-01-", TRACE_ID, PARENT_ID),
+            // Later versions still need well-formed leading fields
+            format!("01-{}-{}-01extra", TRACE_ID, PARENT_ID),
+            format!("01-{}-{}-01-extra", &TRACE_ID[1..], PARENT_ID),
+            // Uppercase and non-hex
+            format!("00-{}-{}-01", TRACE_ID.to_uppercase(), PARENT_ID),
+            format!("00-{}-{}-0g", TRACE_ID, PARENT_ID),
+            String::new(),
+        ];
+        for tp in &invalid {
+            assert!(!valid_traceparent(tp), "{:?}", tp);
+        }
+    }
+
+    #[test]
+    fn generated_ids_are_valid() {
+        for _ in 0..100 {
+            let ids = Ids::generate();
+            assert!(valid_traceparent(&ids.traceparent), "{}", ids.traceparent);
+            assert!(valid_request_id(&ids.request_id), "{}", ids.request_id);
+        }
+    }
+}
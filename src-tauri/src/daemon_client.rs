@@ -0,0 +1,178 @@
+//! Shared HTTP/1.1 client for the daemon and plugin sockets.
+//!
+//! One hyper client with keep-alive pooling serves the proxy, health checks
+//! and SSE streams. The endpoint (socket path, or `tcp://host:port` for a
+//! remote daemon, see `transport`) is hex-encoded into the URI authority
+//! (`unix://<hex>/path`), so the pool keeps idle connections per endpoint and
+//! a studio firing dozens of API calls reuses them instead of reconnecting.
+//!
+//! Hyper does the wire work: chunked bodies with extensions and trailers,
+//! Content-Length and read-to-EOF framing are all decoded here, not by callers.
+
+use bytes::Bytes;
+use http_body_util::combinators::BoxBody;
+use http_body_util::{BodyExt, Empty, Full};
+use hyper::body::Incoming;
+use hyper::header::HOST;
+use hyper::http::request;
+use hyper::{Method, Request, Response, Uri};
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::{TokioExecutor, TokioIo, TokioTimer};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use crate::transport;
+
+const POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+const POOL_MAX_IDLE_PER_SOCKET: usize = 8;
+
+/// Request body type accepted by the shared client.
+pub type Body = BoxBody<Bytes, std::io::Error>;
+
+/// Error returned by the pooled client (connect, write or parse failures).
+pub type Error = hyper_util::client::legacy::Error;
+
+/// The pooled client; replaced by [`reset_pool`].
+static CLIENT: Mutex<Option<Client<Connector, Body>>> = Mutex::new(None);
+
+fn client() -> Client<Connector, Body> {
+    let mut client = CLIENT.lock().unwrap_or_else(|e| e.into_inner());
+    client
+        .get_or_insert_with(|| {
+            Client::builder(TokioExecutor::new())
+                .pool_idle_timeout(POOL_IDLE_TIMEOUT)
+                .pool_max_idle_per_host(POOL_MAX_IDLE_PER_SOCKET)
+                .pool_timer(TokioTimer::new())
+                // The authority is the encoded socket path; Host is set per request
+                .set_host(false)
+                .build(Connector)
+        })
+        .clone()
+}
+
+/// Start over with an empty pool, so no request reuses a connection made
+/// under old settings (TLS options changed). Requests in flight finish on
+/// the connections they have.
+pub fn reset_pool() {
+    *CLIENT.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Run a client future to completion from a plain (non-async) thread.
+///
+/// Connections are owned by the Tauri async runtime so they can be pooled
+/// across callers; this drives the future on that runtime.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    tauri::async_runtime::block_on(future)
+}
+
+/// Start building a request for `path_and_query` on the daemon at `endpoint`.
+pub fn request(method: Method, endpoint: &str, path_and_query: &str) -> request::Builder {
+    Request::builder()
+        .method(method)
+        .uri(socket_uri(endpoint, path_and_query))
+        .header(HOST, transport::host_header(endpoint))
+}
+
+/// Send a request over the pooled connection for its endpoint.
+pub async fn send(request: Request<Body>) -> Result<Response<Incoming>, Error> {
+    client().request(request).await
+}
+
+/// An empty request body.
+pub fn empty() -> Body {
+    Empty::<Bytes>::new().map_err(|never| match never {}).boxed()
+}
+
+/// A request body already held in memory.
+pub fn full(data: Bytes) -> Body {
+    Full::new(data).map_err(|never| match never {}).boxed()
+}
+
+/// Next chunk of response data, skipping trailer frames.
+/// Returns `None` once the body is complete.
+pub async fn next_chunk(body: &mut Incoming) -> Option<Result<Bytes, hyper::Error>> {
+    loop {
+        match body.frame().await? {
+            Ok(frame) => {
+                if let Ok(data) = frame.into_data() {
+                    return Some(Ok(data));
+                }
+            }
+            Err(e) => return Some(Err(e)),
+        }
+    }
+}
+
+/// Read a whole response body, giving up if the daemon goes quiet for
+/// longer than `idle_timeout` between chunks.
+pub async fn read_to_end(
+    body: &mut Incoming,
+    idle_timeout: Duration,
+) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut buf = Vec::new();
+    while let Some(chunk) = tokio::time::timeout(idle_timeout, next_chunk(body))
+        .await
+        .map_err(|_| "response body timed out")?
+    {
+        buf.extend_from_slice(&chunk?);
+    }
+    Ok(buf)
+}
+
+/// Rough on-the-wire size of a request head, for the traffic counters.
+pub fn head_len<B>(request: &Request<B>) -> usize {
+    let line = request.method().as_str().len()
+        + request.uri().path_and_query().map(|p| p.as_str().len()).unwrap_or(1)
+        + " HTTP/1.1\r\n ".len();
+    let headers: usize = request
+        .headers()
+        .iter()
+        .map(|(k, v)| k.as_str().len() + v.len() + 4)
+        .sum();
+    line + headers + 2
+}
+
+/// Build `unix://<hex(endpoint)><path_and_query>`.
+fn socket_uri(endpoint: &str, path_and_query: &str) -> String {
+    let host: String = endpoint.bytes().map(|b| format!("{:02x}", b)).collect();
+    format!("unix://{}{}", host, path_and_query)
+}
+
+/// Recover the endpoint from a `unix://<hex>` URI authority.
+fn endpoint_from_uri(uri: &Uri) -> std::io::Result<String> {
+    let invalid = || std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("not a socket URI: {}", uri));
+    let host = uri.host().ok_or_else(invalid)?;
+    if uri.scheme_str() != Some("unix") || host.len() % 2 != 0 {
+        return Err(invalid());
+    }
+    let bytes = (0..host.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&host[i..i + 2], 16))
+        .collect::<Result<Vec<u8>, _>>()
+        .map_err(|_| invalid())?;
+    String::from_utf8(bytes).map_err(|_| invalid())
+}
+
+/// Connector that dials the endpoint encoded in the request URI.
+#[derive(Clone, Copy)]
+struct Connector;
+
+impl tower_service::Service<Uri> for Connector {
+    type Response = TokioIo<transport::Stream>;
+    type Error = std::io::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        Box::pin(async move {
+            let endpoint = endpoint_from_uri(&uri)?;
+            Ok(TokioIo::new(transport::connect(&endpoint).await?))
+        })
+    }
+}
@@ -0,0 +1,294 @@
+//! Named daemon connection profiles, switchable at runtime.
+//!
+//! Profiles live in `~/.hecate/config/daemon-profiles.json`:
+//!
+//! ```json
+//! {
+//!     "version": 1,
+//!     "active": "dev",
+//!     "profiles": {
+//!         "dev": { "label": "Dev daemon", "socket": "~/.hecate-dev/hecate-daemon/sockets/api.sock" },
+//!         "staging": { "socket": "/run/hecate-staging/api.sock" },
+//!         "home-node": {
+//!             "url": "tcp://node.example.net:4443",
+//!             "tls": { "ca": "~/.hecate/tls/ca.pem", "cert": "~/.hecate/tls/me.pem", "key": "~/.hecate/tls/me-key.pem" }
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! A profile names either a local `socket` or a remote `url` reached over
+//! TLS (see `transport`); `tls` is optional and only applies to `url`.
+//!
+//! The built-in `default` profile keeps the usual socket resolution
+//! (`HECATE_SOCKET_PATH`, `/run/hecate`, `~/.hecate`). The profile picked
+//! with `switch_daemon_profile` is remembered in the hecate-web state dir
+//! and wins over `active` from the file.
+//!
+//! A switch re-targets everything that talks to the main daemon: the proxy
+//! resolves the socket per request, `daemon_watcher` moves its inotify watch,
+//! and `daemon_streaming` drops its stream and reconnects. The frontend gets
+//! `daemon-profile-changed`.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::{OnceLock, RwLock};
+use tauri::{AppHandle, Emitter};
+use tokio::sync::watch;
+
+use crate::config_watcher;
+use crate::daemon_client;
+use crate::daemon_watcher;
+use crate::session_identity;
+use crate::socket_proxy;
+use crate::transport::{self, TlsOptions};
+
+pub const PROFILES_FILE: &str = "daemon-profiles.json";
+const PROFILES_VERSION: u32 = 1;
+const ACTIVE_FILE: &str = "daemon-profile";
+pub const DEFAULT_PROFILE: &str = "default";
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct Profile {
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub socket: Option<String>,
+    /// `tcp://host:port` of a remote daemon.
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub tls: Option<TlsOptions>,
+}
+
+impl Profile {
+    /// Socket path or `tcp://` URL this profile connects to.
+    fn endpoint(&self) -> Option<String> {
+        match (&self.url, &self.socket) {
+            (Some(url), _) => Some(url.clone()),
+            (None, Some(socket)) => Some(expand_home(socket)),
+            (None, None) => None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ProfilesFile {
+    version: u32,
+    #[serde(default)]
+    active: Option<String>,
+    #[serde(default)]
+    profiles: BTreeMap<String, Profile>,
+}
+
+struct Profiles {
+    profiles: BTreeMap<String, Profile>,
+    active: String,
+}
+
+/// What the frontend sees for a profile.
+#[derive(Serialize, Clone)]
+pub struct ProfileInfo {
+    pub name: String,
+    pub label: Option<String>,
+    /// Socket path, or `tcp://host:port` for a remote daemon.
+    pub endpoint: String,
+    pub active: bool,
+}
+
+static PROFILES: OnceLock<RwLock<Profiles>> = OnceLock::new();
+static CHANGED: OnceLock<watch::Sender<String>> = OnceLock::new();
+
+fn profiles() -> &'static RwLock<Profiles> {
+    PROFILES.get_or_init(|| RwLock::new(load()))
+}
+
+fn changed() -> &'static watch::Sender<String> {
+    CHANGED.get_or_init(|| watch::channel(active_name()).0)
+}
+
+/// Receiver that is notified with the new profile name on every switch.
+pub fn subscribe() -> watch::Receiver<String> {
+    changed().subscribe()
+}
+
+/// Name of the active profile.
+pub fn active_name() -> String {
+    profiles()
+        .read()
+        .map(|p| p.active.clone())
+        .unwrap_or_else(|_| DEFAULT_PROFILE.to_string())
+}
+
+/// Endpoint of the active profile, or `None` for the built-in default.
+pub fn active_endpoint() -> Option<String> {
+    let profiles = profiles().read().ok()?;
+    profiles.profiles.get(&profiles.active).and_then(Profile::endpoint)
+}
+
+/// Tauri command: list the configured profiles, default first.
+#[tauri::command]
+pub fn list_daemon_profiles() -> Vec<ProfileInfo> {
+    let Ok(profiles) = profiles().read() else {
+        return Vec::new();
+    };
+    let mut list = Vec::new();
+    if !profiles.profiles.contains_key(DEFAULT_PROFILE) {
+        list.push(ProfileInfo {
+            name: DEFAULT_PROFILE.to_string(),
+            label: None,
+            endpoint: socket_proxy::default_socket_path(),
+            active: profiles.active == DEFAULT_PROFILE,
+        });
+    }
+    for (name, profile) in &profiles.profiles {
+        list.push(info(name, profile, &profiles.active));
+    }
+    list
+}
+
+/// Tauri command: make `name` the active profile and re-target the daemon
+/// connections.
+#[tauri::command]
+pub fn switch_daemon_profile(app: AppHandle, name: String) -> Result<ProfileInfo, String> {
+    {
+        let mut profiles = profiles().write().map_err(|e| e.to_string())?;
+        if name != DEFAULT_PROFILE && !profiles.profiles.contains_key(&name) {
+            return Err(format!("unknown daemon profile: {}", name));
+        }
+        profiles.active = name.clone();
+    }
+    if let Err(e) = save_active(&name) {
+        eprintln!("[daemon-profiles] could not remember active profile: {}", e);
+    }
+    let current = list_daemon_profiles()
+        .into_iter()
+        .find(|p| p.active)
+        .ok_or("active profile vanished")?;
+    eprintln!("[daemon-profiles] switched to {} ({})", current.name, current.endpoint);
+    retarget(&app, &current);
+    Ok(current)
+}
+
+/// Re-read the profiles file. Called by the config watcher on change; if
+/// the active profile now points somewhere else, connections follow it.
+pub fn reload(app: &AppHandle) {
+    let before = active_endpoint();
+    let fresh = load();
+    if let Ok(mut current) = profiles().write() {
+        *current = fresh;
+    }
+    if active_endpoint() != before {
+        if let Some(current) = list_daemon_profiles().into_iter().find(|p| p.active) {
+            eprintln!("[daemon-profiles] active profile now {} ({})", current.name, current.endpoint);
+            retarget(app, &current);
+        }
+    }
+}
+
+fn retarget(app: &AppHandle, current: &ProfileInfo) {
+    session_identity::forget();
+    changed().send_replace(current.name.clone());
+    daemon_watcher::retarget();
+    if let Err(e) = app.emit("daemon-profile-changed", current) {
+        eprintln!("[daemon-profiles] emit failed: {}", e);
+    }
+}
+
+fn info(name: &str, profile: &Profile, active: &str) -> ProfileInfo {
+    ProfileInfo {
+        name: name.to_string(),
+        label: profile.label.clone(),
+        endpoint: profile.endpoint().unwrap_or_default(),
+        active: name == active,
+    }
+}
+
+fn profiles_path() -> PathBuf {
+    config_watcher::config_dir().join(PROFILES_FILE)
+}
+
+fn active_path() -> PathBuf {
+    config_watcher::state_dir().join(ACTIVE_FILE)
+}
+
+fn save_active(name: &str) -> std::io::Result<()> {
+    std::fs::create_dir_all(config_watcher::state_dir())?;
+    std::fs::write(active_path(), name)
+}
+
+/// Load the profiles file and pick the active profile: the last switch,
+/// else the file's `active`, else the built-in default.
+fn load() -> Profiles {
+    let path = profiles_path();
+    let file = match std::fs::read_to_string(&path) {
+        Ok(content) => match serde_json::from_str::<ProfilesFile>(&content) {
+            Ok(file) if file.version == PROFILES_VERSION => Some(file),
+            Ok(file) => {
+                eprintln!(
+                    "[daemon-profiles] unsupported version {} in {}, ignoring",
+                    file.version,
+                    path.display()
+                );
+                None
+            }
+            Err(e) => {
+                eprintln!("[daemon-profiles] invalid {}: {}, ignoring", path.display(), e);
+                None
+            }
+        },
+        Err(_) => None,
+    };
+    let (mut profiles, file_active) = file.map(|f| (f.profiles, f.active)).unwrap_or_default();
+    profiles.retain(|name, profile| match (&profile.url, &profile.socket) {
+        (Some(url), _) if !transport::is_remote(url) => {
+            eprintln!("[daemon-profiles] {}: url must be tcp://host:port, skipping", name);
+            false
+        }
+        (Some(url), _) => {
+            let tls = profile.tls.clone().map(expand_tls_paths).unwrap_or_default();
+            if transport::configure_tls(url, tls) {
+                // Idle connections to it were set up with the old settings
+                daemon_client::reset_pool();
+            }
+            true
+        }
+        (None, Some(_)) => true,
+        (None, None) => {
+            eprintln!("[daemon-profiles] {}: needs a socket or url, skipping", name);
+            false
+        }
+    });
+
+    let known = |name: &String| name == DEFAULT_PROFILE || profiles.contains_key(name);
+    let remembered = std::fs::read_to_string(active_path())
+        .ok()
+        .map(|s| s.trim().to_string());
+    let active = remembered
+        .filter(known)
+        .or(file_active.filter(known))
+        .unwrap_or_else(|| DEFAULT_PROFILE.to_string());
+
+    if !profiles.is_empty() {
+        eprintln!("[daemon-profiles] {} profiles, active: {}", profiles.len(), active);
+    }
+    Profiles { profiles, active }
+}
+
+fn expand_tls_paths(tls: TlsOptions) -> TlsOptions {
+    TlsOptions {
+        ca: tls.ca.as_deref().map(expand_home),
+        cert: tls.cert.as_deref().map(expand_home),
+        key: tls.key.as_deref().map(expand_home),
+        server_name: tls.server_name,
+    }
+}
+
+/// Expand a leading `~/` to `$HOME/`.
+fn expand_home(path: &str) -> String {
+    match (path.strip_prefix("~/"), std::env::var("HOME")) {
+        (Some(rest), Ok(home)) => format!("{}/{}", home.trim_end_matches('/'), rest),
+        _ => path.to_string(),
+    }
+}
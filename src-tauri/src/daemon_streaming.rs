@@ -1,219 +1,359 @@
 //! Always-on SSE client that connects to the daemon's /api/events endpoint
-//! and forwards domain state changes as Tauri events.
+//! and forwards domain state changes as Tauri events (named per
+//! `event_map`).
 //!
-//! Auto-reconnects on disconnect with a 3-second retry delay.
-//! Uses the same Unix socket resolution as the rest of the app.
+//! Auto-reconnects on disconnect with exponential backoff and jitter,
+//! starting from 3 seconds or whatever the daemon asked for with `retry:`,
+//! and idles while the daemon watcher sees no socket. Progress is reported
+//! as `daemon-stream-state` events and by `get_daemon_stream_state`.
+//!
+//! The last `id:` seen is sent back as `Last-Event-ID` so the daemon can
+//! replay what was missed; when it cannot, it sends a `stream_gap` event and
+//! the shell emits `daemon-stream-gap` so the UI knows to refetch.
+//! Uses the same Unix socket resolution as the rest of the app, and
+//! reconnects at once (starting afresh) when the daemon connection profile
+//! is switched.
 
-use std::io::{BufRead, BufReader, Write};
-use std::os::unix::net::UnixStream;
-use std::time::Duration;
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::http::header::ACCEPT;
+use tauri::http::{HeaderValue, Method};
 use tauri::{AppHandle, Emitter};
+use tokio::sync::watch;
 
+use crate::correlation;
+use crate::daemon_client;
+use crate::daemon_profiles;
+use crate::daemon_watcher;
+use crate::event_buffer;
+use crate::event_map::{self, Forward};
+use crate::har_recorder;
+use crate::response_cache;
+use crate::session_identity;
 use crate::socket_proxy::resolve_socket_path;
+use crate::sse;
 use crate::traffic;
 
+/// First reconnect delay, unless the daemon sets one with `retry:`.
 const RECONNECT_DELAY: Duration = Duration::from_secs(3);
+const MIN_RECONNECT_DELAY: Duration = Duration::from_millis(100);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+/// A connection that lasts this long resets the backoff.
+const STABLE_AFTER: Duration = Duration::from_secs(30);
 const STREAM_PATH: &str = "/api/events";
+const LAST_EVENT_ID: &str = "last-event-id";
+/// Event type the daemon sends when it cannot replay from `Last-Event-ID`.
+const GAP_EVENT: &str = "stream_gap";
+
+/// What carries over from one connection to the next.
+struct Resume {
+    /// The last `id:` the daemon sent, sent back on reconnect.
+    last_event_id: Option<String>,
+    /// Base reconnect delay, as set by the daemon's `retry:`.
+    retry: Duration,
+}
+
+impl Resume {
+    fn new() -> Self {
+        Resume {
+            last_event_id: None,
+            retry: RECONNECT_DELAY,
+        }
+    }
+}
+
+/// Where the event stream is, as reported by `daemon-stream-state`.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum StreamState {
+    /// Opening a connection to the daemon.
+    Connecting { attempt: u32 },
+    /// Receiving events.
+    Connected,
+    /// Waiting to reconnect after a failure or a closed stream.
+    Backoff {
+        attempt: u32,
+        retry_in_ms: u64,
+        error: Option<String>,
+    },
+    /// Not trying: there is no daemon socket to connect to.
+    Stopped,
+}
+
+static STATE: Mutex<StreamState> = Mutex::new(StreamState::Stopped);
+
+/// Tauri command: the event stream's current state.
+#[tauri::command]
+pub fn get_daemon_stream_state() -> StreamState {
+    STATE.lock().map(|s| s.clone()).unwrap_or(StreamState::Stopped)
+}
+
+fn set_state(app: &AppHandle, state: StreamState) {
+    if let Ok(mut current) = STATE.lock() {
+        if *current == state {
+            return;
+        }
+        *current = state.clone();
+    }
+    if let Err(e) = app.emit("daemon-stream-state", &state) {
+        eprintln!("[daemon_streaming] emit failed for daemon-stream-state: {}", e);
+    }
+}
 
 /// Start the background SSE streaming thread.
-/// Runs forever, auto-reconnecting on disconnect.
+/// Runs forever: idles while the daemon socket is absent, and otherwise
+/// reconnects on disconnect with exponential backoff.
 pub fn start(app: AppHandle) {
     std::thread::spawn(move || {
         eprintln!("[daemon_streaming] starting SSE event stream");
+        let mut profile = daemon_profiles::subscribe();
+        let mut socket = daemon_watcher::subscribe_socket();
+        let mut resume = Resume::new();
+        let mut attempt: u32 = 0;
         loop {
-            match connect_and_stream(&app) {
+            // Event ids, backoff and buffered events from the old daemon
+            // mean nothing to the one the profile switched to
+            if profile.has_changed().unwrap_or(false) {
+                resume = Resume::new();
+                attempt = 0;
+                event_buffer::clear();
+            }
+            profile.mark_unchanged();
+            if !*socket.borrow_and_update() {
+                eprintln!("[daemon_streaming] no daemon socket, waiting for it to appear");
+                set_state(&app, StreamState::Stopped);
+                daemon_client::block_on(wait_for_socket(&mut socket, profile.clone()));
+                continue;
+            }
+
+            attempt += 1;
+            set_state(&app, StreamState::Connecting { attempt });
+            let started = Instant::now();
+            let error = match daemon_client::block_on(connect_and_stream(&app, profile.clone(), &mut resume)) {
                 Ok(()) => {
                     eprintln!("[daemon_streaming] stream ended cleanly, reconnecting...");
+                    None
                 }
-                Err(e) => {
-                    eprintln!("[daemon_streaming] connection error: {}, retrying in 3s...", e);
-                }
+                Err(e) => Some(e.to_string()),
+            };
+            // A connection that held up for a while starts the backoff over
+            if started.elapsed() >= STABLE_AFTER {
+                attempt = 1;
+            }
+            // A profile switch skips the retry delay
+            if profile.has_changed().unwrap_or(false) {
+                continue;
             }
-            std::thread::sleep(RECONNECT_DELAY);
+
+            let delay = backoff(resume.retry, attempt);
+            if let Some(e) = &error {
+                eprintln!(
+                    "[daemon_streaming] connection error: {}, retrying in {}ms (attempt {})...",
+                    e,
+                    delay.as_millis(),
+                    attempt
+                );
+            }
+            set_state(
+                &app,
+                StreamState::Backoff {
+                    attempt,
+                    retry_in_ms: delay.as_millis() as u64,
+                    error,
+                },
+            );
+            // Cut short by a profile switch, or by the socket coming or going.
+            // The switch is watched on a clone so the loop still sees it.
+            let mut switched = profile.clone();
+            daemon_client::block_on(async {
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => {}
+                    _ = switched.changed() => {}
+                    _ = socket.changed() => {}
+                }
+            });
         }
     });
 }
 
-fn connect_and_stream(app: &AppHandle) -> Result<(), Box<dyn std::error::Error>> {
-    let socket_path = resolve_socket_path();
-    let mut stream = UnixStream::connect(&socket_path)?;
-    stream.set_read_timeout(None)?;
-
-    let http_req = format!(
-        "GET {} HTTP/1.1\r\nHost: localhost\r\nAccept: text/event-stream\r\nConnection: keep-alive\r\n\r\n",
-        STREAM_PATH
-    );
-    stream.write_all(http_req.as_bytes())?;
-
-    let mut reader = BufReader::new(stream);
-
-    // Read HTTP status line
-    let mut status_line = String::new();
-    reader.read_line(&mut status_line)?;
-    let status_code = parse_status(&status_line);
-
-    if status_code >= 400 {
-        return Err(format!("daemon returned HTTP {}", status_code).into());
-    }
-
-    // Read headers, detect chunked transfer encoding
-    let mut is_chunked = false;
+/// Wait until the daemon socket is there (or the profile switches).
+async fn wait_for_socket(socket: &mut watch::Receiver<bool>, mut profile: watch::Receiver<String>) {
     loop {
-        let mut line = String::new();
-        let n = reader.read_line(&mut line)?;
-        if n == 0 {
-            return Err("connection closed during headers".into());
-        }
-        if line.trim().is_empty() {
-            break;
-        }
-        let lower = line.to_lowercase();
-        if lower.contains("transfer-encoding") && lower.contains("chunked") {
-            is_chunked = true;
+        tokio::select! {
+            changed = socket.changed() => {
+                if changed.is_err() || *socket.borrow() {
+                    return;
+                }
+            }
+            _ = profile.changed() => return,
         }
     }
+}
 
-    eprintln!("[daemon_streaming] connected, chunked={}", is_chunked);
+/// `base` doubled for every attempt after the first, capped, with up to half
+/// again added at random so clients do not all reconnect in step. Never
+/// shorter than `base`, which is what the daemon asked for with `retry:`.
+fn backoff(base: Duration, attempt: u32) -> Duration {
+    let base = base.max(MIN_RECONNECT_DELAY);
+    let exponential = base.saturating_mul(1 << attempt.saturating_sub(1).min(16));
+    let capped = exponential.min(MAX_RECONNECT_DELAY.max(base));
+    capped + capped.mul_f64(0.5 * jitter())
+}
 
-    if is_chunked {
-        read_sse_chunked(app, &mut reader)
-    } else {
-        read_sse_direct(app, &mut reader)
-    }
+/// A number in `[0, 1)`.
+fn jitter() -> f64 {
+    (correlation::random_u64() >> 11) as f64 / (1u64 << 53) as f64
 }
 
-/// Read SSE from a chunked transfer-encoded stream (cowboy default).
-fn read_sse_chunked(
+async fn connect_and_stream(
     app: &AppHandle,
-    reader: &mut BufReader<UnixStream>,
+    mut profile: watch::Receiver<String>,
+    resume: &mut Resume,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let mut leftover = String::new();
-    let mut current_event_type: Option<String> = None;
-    let mut current_data: Option<String> = None;
-
-    loop {
-        // Read chunk size line
-        let mut size_line = String::new();
-        let bytes_read = reader.read_line(&mut size_line)?;
-        if bytes_read == 0 {
-            break;
-        }
+    let socket_path = resolve_socket_path();
 
-        let trimmed = size_line.trim();
-        let size = match usize::from_str_radix(trimmed, 16) {
-            Ok(s) => s,
-            Err(_) => continue,
-        };
+    let mut request = daemon_client::request(Method::GET, &socket_path, STREAM_PATH)
+        .header(ACCEPT, "text/event-stream");
+    if let Some(id) = resume.last_event_id.as_deref().and_then(|id| HeaderValue::from_str(id).ok()) {
+        request = request.header(LAST_EVENT_ID, id);
+    }
+    let mut request = request.body(daemon_client::empty())?;
+    let ids = correlation::Ids::generate();
+    ids.apply(request.headers_mut());
+    traffic::record_tx(daemon_client::head_len(&request) as u64);
+    let capture = har_recorder::begin(
+        request.method(),
+        &format!("hecate://localhost{}", STREAM_PATH),
+        request.headers(),
+        &[],
+    );
 
-        if size == 0 {
-            break; // Final chunk
-        }
+    let response = daemon_client::send(request).await?;
+    let status_code = response.status().as_u16();
+    if let Some(c) = &capture {
+        c.response(response.status(), response.headers());
+    }
 
-        let mut chunk_buf = vec![0u8; size];
-        std::io::Read::read_exact(reader, &mut chunk_buf)?;
-        traffic::record_rx(size as u64);
+    if status_code >= 400 {
+        return Err(format!("daemon returned HTTP {}", status_code).into());
+    }
 
-        // Read trailing CRLF
-        let mut _trail = String::new();
-        let _ = reader.read_line(&mut _trail);
+    set_state(app, StreamState::Connected);
+    match &resume.last_event_id {
+        Some(id) => eprintln!("[daemon_streaming] connected (request {}), resuming after {}", ids.request_id, id),
+        None => eprintln!("[daemon_streaming] connected (request {})", ids.request_id),
+    }
 
-        let chunk_str = String::from_utf8_lossy(&chunk_buf);
-        leftover.push_str(&chunk_str);
+    let mut body = response.into_body();
+    let mut decoder = sse::Decoder::resuming(resume.last_event_id.as_deref());
 
-        // Process complete lines
-        while let Some(newline_pos) = leftover.find('\n') {
-            let line = leftover[..newline_pos].trim_end_matches('\r').to_string();
-            leftover = leftover[newline_pos + 1..].to_string();
+    loop {
+        let chunk = tokio::select! {
+            chunk = daemon_client::next_chunk(&mut body) => chunk,
+            _ = profile.changed() => {
+                eprintln!("[daemon_streaming] daemon profile switched to {}, reconnecting", *profile.borrow());
+                return Ok(());
+            }
+        };
+        let Some(chunk) = chunk else { break };
+        let chunk = chunk?;
+        traffic::record_rx(chunk.len() as u64);
+        if let Some(c) = &capture {
+            c.body(&chunk);
+        }
 
-            process_sse_line(app, &line, &mut current_event_type, &mut current_data);
+        let events = decoder.feed(&chunk);
+        resume.last_event_id = decoder.last_event_id().map(str::to_string);
+        if let Some(retry) = decoder.retry() {
+            resume.retry = retry;
+        }
+        for event in events {
+            if event.event_type == GAP_EVENT {
+                emit_gap(app, &event.data, resume);
+            } else {
+                dispatch_event(app, &event.event_type, &event.data, event.id.as_deref());
+            }
         }
     }
 
     Ok(())
 }
 
-/// Read SSE from a non-chunked stream (fallback).
-fn read_sse_direct(
-    app: &AppHandle,
-    reader: &mut BufReader<UnixStream>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let mut current_event_type: Option<String> = None;
-    let mut current_data: Option<String> = None;
+/// The daemon could not replay from the id we sent: events were lost.
+fn emit_gap(app: &AppHandle, data: &str, resume: &Resume) {
+    eprintln!(
+        "[daemon_streaming] daemon could not replay after {}, events were missed",
+        resume.last_event_id.as_deref().unwrap_or("(none)")
+    );
+    response_cache::invalidate_for_event(GAP_EVENT);
+    let detail = serde_json::from_str(data).unwrap_or_else(|_| serde_json::Value::String(data.to_string()));
+    let payload = serde_json::json!({ "last_event_id": resume.last_event_id, "detail": detail });
+    if let Err(e) = app.emit("daemon-stream-gap", payload) {
+        eprintln!("[daemon_streaming] emit failed for daemon-stream-gap: {}", e);
+    }
+}
 
-    loop {
-        let mut line = String::new();
-        let n = reader.read_line(&mut line)?;
-        if n == 0 {
-            break;
-        }
-        traffic::record_rx(n as u64);
-        let trimmed = line.trim_end_matches('\n').trim_end_matches('\r').to_string();
-        process_sse_line(app, &trimmed, &mut current_event_type, &mut current_data);
+/// Forward an SSE event as the Tauri event `event_map` names for its type,
+/// or inside the generic `daemon-event` envelope, and keep it in the
+/// recent-event buffer.
+fn dispatch_event(app: &AppHandle, event_type: &str, data: &str, id: Option<&str>) {
+    response_cache::invalidate_for_event(event_type);
+    if event_type == "identity_changed" {
+        let payload = serde_json::from_str(data).unwrap_or(serde_json::Value::Null);
+        session_identity::on_identity_changed(&payload);
     }
 
-    Ok(())
+    match event_map::forward(event_type) {
+        Forward::Silenced => {}
+        Forward::Named(name) => match serde_json::from_str::<serde_json::Value>(data) {
+            Ok(value) => {
+                event_buffer::record(event_type, &name, &value, id);
+                emit(app, &name, value);
+            }
+            Err(e) => {
+                eprintln!("[daemon_streaming] JSON parse error for {}: {}", name, e);
+            }
+        },
+        Forward::Generic => {
+            // Not every daemon event need carry JSON; pass the raw text on
+            let data = serde_json::from_str(data).unwrap_or_else(|_| serde_json::Value::String(data.to_string()));
+            let seq = event_buffer::record(event_type, event_map::GENERIC_EVENT, &data, id);
+            emit(
+                app,
+                event_map::GENERIC_EVENT,
+                serde_json::json!({ "type": event_type, "data": data, "seq": seq }),
+            );
+        }
+    }
 }
 
-/// Process a single SSE line per the SSE spec.
-/// - `event: <type>` sets the event type
-/// - `data: <payload>` sets the data
-/// - Empty line dispatches the accumulated event
-/// - `: comment` lines are ignored (heartbeats)
-fn process_sse_line(
-    app: &AppHandle,
-    line: &str,
-    current_event_type: &mut Option<String>,
-    current_data: &mut Option<String>,
-) {
-    if line.is_empty() {
-        // Blank line = dispatch event
-        if let Some(data) = current_data.take() {
-            let event_type = current_event_type.take().unwrap_or_default();
-            dispatch_event(app, &event_type, &data);
-        }
-        *current_event_type = None;
-    } else if let Some(comment) = line.strip_prefix(':') {
-        // SSE comment (heartbeat, etc.) — ignore
-        let _ = comment;
-    } else if let Some(value) = line.strip_prefix("event: ") {
-        *current_event_type = Some(value.trim().to_string());
-    } else if let Some(value) = line.strip_prefix("event:") {
-        *current_event_type = Some(value.trim().to_string());
-    } else if let Some(value) = line.strip_prefix("data: ") {
-        *current_data = Some(value.to_string());
-    } else if let Some(value) = line.strip_prefix("data:") {
-        *current_data = Some(value.trim().to_string());
+fn emit(app: &AppHandle, tauri_event: &str, payload: serde_json::Value) {
+    if let Err(e) = app.emit(tauri_event, payload) {
+        eprintln!("[daemon_streaming] emit failed for {}: {}", tauri_event, e);
     }
 }
 
-/// Map SSE event type to Tauri event name and emit.
-fn dispatch_event(app: &AppHandle, event_type: &str, data: &str) {
-    let tauri_event = match event_type {
-        "realm_join_status" => "daemon-realm-join-status",
-        "identity_changed" => "daemon-identity-changed",
-        "settings_changed" => "daemon-settings-changed",
-        other => {
-            eprintln!("[daemon_streaming] unknown event type: {}", other);
-            return;
-        }
-    };
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    match serde_json::from_str::<serde_json::Value>(data) {
-        Ok(value) => {
-            if let Err(e) = app.emit(tauri_event, value) {
-                eprintln!("[daemon_streaming] emit failed for {}: {}", tauri_event, e);
+    #[test]
+    fn backoff_never_undercuts_retry() {
+        let retry = Duration::from_secs(5);
+        for attempt in 1..=20 {
+            let capped = retry
+                .saturating_mul(1 << (attempt - 1).min(16))
+                .min(MAX_RECONNECT_DELAY.max(retry));
+            for _ in 0..50 {
+                let delay = backoff(retry, attempt);
+                assert!(delay >= capped, "attempt {}: {:?} < {:?}", attempt, delay, capped);
+                assert!(delay <= capped.mul_f64(1.5), "attempt {}: {:?}", attempt, delay);
             }
         }
-        Err(e) => {
-            eprintln!("[daemon_streaming] JSON parse error for {}: {}", tauri_event, e);
-        }
+        // A retry above the cap is still honoured
+        assert!(backoff(Duration::from_secs(90), 3) >= Duration::from_secs(90));
+        // ...and a tiny one is raised to the floor
+        assert!(backoff(Duration::ZERO, 1) >= MIN_RECONNECT_DELAY);
     }
 }
-
-fn parse_status(status_line: &str) -> u16 {
-    status_line
-        .split_whitespace()
-        .nth(1)
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(500)
-}
@@ -1,12 +1,16 @@
 use notify::{Event, EventKind, RecursiveMode, Watcher};
-use std::path::PathBuf;
-use std::sync::{mpsc, Mutex};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Mutex, OnceLock};
 use std::time::Duration;
 use tauri::Emitter;
+use tokio::sync::watch;
 
+use crate::daemon_client;
+use crate::offline_queue;
+use crate::session_identity;
 use crate::socket_proxy;
+use crate::transport;
 
-const SOCKET_NAME: &str = "api.sock";
 const STARTUP_RETRY_DELAY: Duration = Duration::from_millis(500);
 const STARTUP_RETRIES: u32 = 10;
 const RECHECK_INTERVAL: Duration = Duration::from_secs(30);
@@ -23,14 +27,49 @@ pub fn get_cached_health() -> Option<serde_json::Value> {
     HEALTH_CACHE.lock().ok().and_then(|cache| cache.clone())
 }
 
-fn update_cache(health: &Option<serde_json::Value>) {
-    if let Ok(mut cache) = HEALTH_CACHE.lock() {
-        *cache = health.clone();
+/// Whether the daemon socket is there, as last seen by the watcher.
+static SOCKET_PRESENT: OnceLock<watch::Sender<bool>> = OnceLock::new();
+
+fn socket_present() -> &'static watch::Sender<bool> {
+    SOCKET_PRESENT.get_or_init(|| watch::channel(transport::is_available(&socket_path())).0)
+}
+
+/// Receiver that is notified whenever the daemon socket appears or vanishes.
+pub fn subscribe_socket() -> watch::Receiver<bool> {
+    socket_present().subscribe()
+}
+
+fn set_socket_present(present: bool) {
+    socket_present().send_if_modified(|current| std::mem::replace(current, present) != present);
+}
+
+/// Whether the last health check succeeded. `false` until the first one
+/// completes and whenever the daemon socket is gone.
+pub fn is_healthy() -> bool {
+    HEALTH_CACHE.lock().map(|cache| cache.is_some()).unwrap_or(false)
+}
+
+/// Store the latest health; returns whether the daemon just came back.
+fn update_cache(health: &Option<serde_json::Value>) -> bool {
+    match HEALTH_CACHE.lock() {
+        Ok(mut cache) => {
+            let recovered = cache.is_none() && health.is_some();
+            *cache = health.clone();
+            recovered
+        }
+        Err(_) => false,
     }
 }
 
 fn emit_health(app: &tauri::AppHandle, health: Option<serde_json::Value>) {
-    update_cache(&health);
+    if update_cache(&health) {
+        // Know who we are before replaying anything on the user's behalf
+        daemon_client::block_on(session_identity::refresh());
+    }
+    if health.is_some() {
+        // Also after a replay paused on a healthy daemon
+        offline_queue::resume(app);
+    }
     match app.emit("daemon-health", &health) {
         Ok(_) => {
             let label = if health.is_some() { "connected" } else { "unavailable" };
@@ -68,41 +107,83 @@ fn wait_for_healthy(app: &tauri::AppHandle) {
     emit_health(app, None);
 }
 
-fn socket_dir() -> PathBuf {
-    if let Ok(home) = std::env::var("HOME") {
-        PathBuf::from(home).join(".hecate").join("hecate-daemon").join("sockets")
-    } else {
-        PathBuf::from("/run/hecate")
+/// Messages for the watcher thread: filesystem events, or a request to
+/// follow a new daemon socket after a connection-profile switch.
+enum WatchMsg {
+    Fs(Event),
+    Retarget,
+}
+
+static WATCHER_TX: Mutex<Option<mpsc::Sender<WatchMsg>>> = Mutex::new(None);
+
+/// Point the watcher at the active profile's socket. Called on profile switch.
+pub fn retarget() {
+    if let Ok(tx) = WATCHER_TX.lock() {
+        if let Some(tx) = tx.as_ref() {
+            tx.send(WatchMsg::Retarget).ok();
+        }
+    }
+}
+
+fn socket_path() -> String {
+    socket_proxy::resolve_socket_path()
+}
+
+/// Directory to watch for the socket appearing and vanishing; `None` for a
+/// remote daemon, which is only polled.
+fn socket_dir(sock: &str) -> Option<PathBuf> {
+    if transport::is_remote(sock) {
+        return None;
     }
+    Some(
+        Path::new(sock)
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| PathBuf::from("/run/hecate")),
+    )
 }
 
-fn socket_path() -> PathBuf {
-    socket_dir().join(SOCKET_NAME)
+fn watch_dir(watcher: &mut impl Watcher, dir: &Option<PathBuf>) {
+    let Some(dir) = dir else {
+        eprintln!("[watcher] remote daemon, polling every {}s", RECHECK_INTERVAL.as_secs());
+        return;
+    };
+    std::fs::create_dir_all(dir).ok();
+    match watcher.watch(dir.as_path(), RecursiveMode::NonRecursive) {
+        Ok(()) => eprintln!("[watcher] inotify watching {}", dir.display()),
+        Err(e) => eprintln!("[watcher] failed to watch {}: {}", dir.display(), e),
+    }
+}
+
+/// Emit the initial state for a socket: healthy once it answers, or
+/// unavailable if it is not there.
+fn check_socket(app: &tauri::AppHandle, sock: &str) {
+    let present = transport::is_available(sock);
+    set_socket_present(present);
+    if present {
+        eprintln!("[watcher] checking daemon at {}", sock);
+        wait_for_healthy(app);
+    } else {
+        eprintln!("[watcher] socket NOT found at {}", sock);
+        emit_health(app, None);
+    }
 }
 
 pub fn start(app: tauri::AppHandle) {
     eprintln!("[watcher] starting daemon watcher");
     std::thread::spawn(move || {
-        let dir = socket_dir();
-        let sock = socket_path();
-        eprintln!("[watcher] watching dir: {}", dir.display());
-
-        std::fs::create_dir_all(&dir).ok();
+        let mut sock = socket_path();
+        let mut dir = socket_dir(&sock);
 
         // Emit initial state
-        if sock.exists() {
-            eprintln!("[watcher] socket exists at startup");
-            wait_for_healthy(&app);
-        } else {
-            eprintln!("[watcher] socket NOT found at startup");
-            emit_health(&app, None);
-        }
+        check_socket(&app, &sock);
 
         // Set up inotify watcher
         let (tx, rx) = mpsc::channel();
+        let fs_tx = tx.clone();
         let mut watcher = match notify::recommended_watcher(move |res: Result<Event, _>| {
             if let Ok(event) = res {
-                tx.send(event).ok();
+                fs_tx.send(WatchMsg::Fs(event)).ok();
             }
         }) {
             Ok(w) => w,
@@ -111,22 +192,21 @@ pub fn start(app: tauri::AppHandle) {
                 return;
             }
         };
-
-        if let Err(e) = watcher.watch(dir.as_path(), RecursiveMode::NonRecursive) {
-            eprintln!("[watcher] failed to watch {}: {}", dir.display(), e);
-            return;
+        watch_dir(&mut watcher, &dir);
+        if let Ok(mut slot) = WATCHER_TX.lock() {
+            *slot = Some(tx);
         }
-        eprintln!("[watcher] inotify watching {}", dir.display());
 
         // inotify for instant detection + 30s periodic recheck as safety net.
         // Covers stale sockets, daemon restarts that reuse the same path, etc.
         loop {
             match rx.recv_timeout(RECHECK_INTERVAL) {
-                Ok(event) => {
+                Ok(WatchMsg::Fs(event)) => {
+                    let sock_name = Path::new(&sock).file_name();
                     let dominated = event
                         .paths
                         .iter()
-                        .any(|p| p.file_name().map(|n| n == SOCKET_NAME).unwrap_or(false));
+                        .any(|p| p.file_name().is_some_and(|n| Some(n) == sock_name));
 
                     if !dominated {
                         continue;
@@ -136,16 +216,38 @@ pub fn start(app: tauri::AppHandle) {
 
                     match event.kind {
                         EventKind::Create(_) | EventKind::Modify(_) => {
+                            set_socket_present(true);
                             wait_for_healthy(&app);
                         }
                         EventKind::Remove(_) => {
+                            set_socket_present(false);
                             emit_health(&app, None);
                         }
                         _ => {}
                     }
                 }
+                Ok(WatchMsg::Retarget) => {
+                    let new_sock = socket_path();
+                    if new_sock == sock {
+                        continue;
+                    }
+                    let new_dir = socket_dir(&new_sock);
+                    if new_dir != dir {
+                        if let Some(old) = &dir {
+                            watcher.unwatch(old.as_path()).ok();
+                        }
+                        watch_dir(&mut watcher, &new_dir);
+                    }
+                    sock = new_sock;
+                    dir = new_dir;
+                    // The old daemon's health no longer applies
+                    emit_health(&app, None);
+                    check_socket(&app, &sock);
+                }
                 Err(mpsc::RecvTimeoutError::Timeout) => {
-                    if sock.exists() {
+                    let present = transport::is_available(&sock);
+                    set_socket_present(present);
+                    if present {
                         match try_health_check() {
                             Some(h) => emit_health(&app, Some(h)),
                             None => emit_health(&app, None),
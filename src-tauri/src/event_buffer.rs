@@ -0,0 +1,223 @@
+//! Recent daemon events, kept for views that mount after they were emitted.
+//!
+//! Every event `daemon_streaming` forwards is also recorded here with a
+//! sequence number, in a small ring per event type so a chatty type cannot
+//! push out the others. `get_recent_daemon_events` returns the latest event
+//! of each type, or the events after a sequence number a page at a time, so
+//! a newly mounted route or plugin component can rebuild its state without
+//! asking the daemon again. A `since` query reports when events after that
+//! number are no longer held, so the caller knows to refetch from the daemon
+//! instead. The buffer is emptied when the daemon profile switches;
+//! sequence numbers keep counting up.
+
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Events kept per type.
+const PER_TYPE: usize = 16;
+/// Event types kept; the one heard from least recently goes first.
+const MAX_TYPES: usize = 128;
+/// Events returned by a `since` query when no limit is given.
+const DEFAULT_LIMIT: usize = 100;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct BufferedEvent {
+    pub seq: u64,
+    /// The SSE event type.
+    #[serde(rename = "type")]
+    pub event_type: String,
+    /// The Tauri event it was emitted as.
+    pub event: String,
+    /// The payload as emitted (for `daemon-event`, the envelope's `data`).
+    pub data: serde_json::Value,
+    /// The SSE event id, if the daemon sent one.
+    pub id: Option<String>,
+    pub received_at_ms: u64,
+}
+
+/// Result of `get_recent_daemon_events`.
+#[derive(Serialize, Clone, Debug)]
+pub struct RecentEvents {
+    pub events: Vec<BufferedEvent>,
+    /// Events after `since` were dropped from the buffer before this query;
+    /// `events` is not the complete sequence.
+    pub gap: bool,
+    /// More events after `since` are held than `limit` allowed; ask again
+    /// with the last returned `seq`.
+    pub truncated: bool,
+}
+
+#[derive(Default)]
+struct Buffer {
+    last_seq: u64,
+    /// Highest sequence number no longer held (ring overflow, type eviction
+    /// or `clear`).
+    dropped_seq: u64,
+    by_type: HashMap<String, VecDeque<BufferedEvent>>,
+}
+
+static BUFFER: Mutex<Option<Buffer>> = Mutex::new(None);
+
+fn with_buffer<R>(f: impl FnOnce(&mut Buffer) -> R) -> R {
+    let mut buffer = BUFFER.lock().unwrap_or_else(|e| e.into_inner());
+    f(buffer.get_or_insert_with(Buffer::default))
+}
+
+impl Buffer {
+    fn record(&mut self, event_type: &str, event: &str, data: &serde_json::Value, id: Option<&str>) -> u64 {
+        self.last_seq += 1;
+        let seq = self.last_seq;
+        if !self.by_type.contains_key(event_type) && self.by_type.len() >= MAX_TYPES {
+            let stalest = self
+                .by_type
+                .iter()
+                .min_by_key(|(_, ring)| ring.back().map(|e| e.seq).unwrap_or(0))
+                .map(|(t, _)| t.clone());
+            if let Some(ring) = stalest.and_then(|t| self.by_type.remove(&t)) {
+                let newest = ring.back().map(|e| e.seq).unwrap_or(0);
+                self.dropped_seq = self.dropped_seq.max(newest);
+            }
+        }
+        let ring = self.by_type.entry(event_type.to_string()).or_default();
+        if ring.len() >= PER_TYPE {
+            if let Some(dropped) = ring.pop_front() {
+                self.dropped_seq = self.dropped_seq.max(dropped.seq);
+            }
+        }
+        ring.push_back(BufferedEvent {
+            seq,
+            event_type: event_type.to_string(),
+            event: event.to_string(),
+            data: data.clone(),
+            id: id.map(str::to_string),
+            received_at_ms: now_ms(),
+        });
+        seq
+    }
+
+    fn clear(&mut self) {
+        self.by_type.clear();
+        self.dropped_seq = self.last_seq;
+    }
+
+    fn recent(&self, since: Option<u64>, limit: Option<usize>) -> RecentEvents {
+        let Some(since) = since else {
+            let mut events: Vec<BufferedEvent> =
+                self.by_type.values().filter_map(|ring| ring.back().cloned()).collect();
+            events.sort_by_key(|e| e.seq);
+            return RecentEvents { events, gap: false, truncated: false };
+        };
+        let mut events: Vec<BufferedEvent> = self
+            .by_type
+            .values()
+            .flat_map(|ring| ring.iter().filter(|e| e.seq > since).cloned())
+            .collect();
+        events.sort_by_key(|e| e.seq);
+        let limit = limit.unwrap_or(DEFAULT_LIMIT);
+        let truncated = events.len() > limit;
+        events.truncate(limit);
+        RecentEvents {
+            events,
+            gap: self.dropped_seq > since,
+            truncated,
+        }
+    }
+}
+
+/// Record a forwarded event; returns its sequence number.
+pub fn record(event_type: &str, event: &str, data: &serde_json::Value, id: Option<&str>) -> u64 {
+    with_buffer(|buffer| buffer.record(event_type, event, data, id))
+}
+
+/// Forget everything recorded; the events belong to another daemon.
+pub fn clear() {
+    with_buffer(Buffer::clear);
+}
+
+/// Tauri command: recent daemon events, oldest first.
+///
+/// Without `since`, the latest event of each type. With `since`, the oldest
+/// `limit` events after that sequence number, so a caller pages forward by
+/// passing the last `seq` it got back; `gap` is set when some of the events
+/// after `since` are no longer held.
+#[tauri::command]
+pub fn get_recent_daemon_events(since: Option<u64>, limit: Option<usize>) -> RecentEvents {
+    with_buffer(|buffer| buffer.recent(since, limit))
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn seqs(recent: &RecentEvents) -> Vec<u64> {
+        recent.events.iter().map(|e| e.seq).collect()
+    }
+
+    #[test]
+    fn since_pages_forward_from_the_oldest() {
+        let mut buffer = Buffer::default();
+        for i in 0..10 {
+            buffer.record(if i % 2 == 0 { "a" } else { "b" }, "daemon-event", &json!(i), None);
+        }
+
+        let page = buffer.recent(Some(0), Some(4));
+        assert_eq!(seqs(&page), vec![1, 2, 3, 4]);
+        assert!(page.truncated);
+        assert!(!page.gap);
+
+        let page = buffer.recent(Some(4), Some(4));
+        assert_eq!(seqs(&page), vec![5, 6, 7, 8]);
+        assert!(page.truncated);
+
+        let page = buffer.recent(Some(8), Some(4));
+        assert_eq!(seqs(&page), vec![9, 10]);
+        assert!(!page.truncated);
+        assert!(!page.gap);
+
+        let latest = buffer.recent(None, None);
+        assert_eq!(seqs(&latest), vec![9, 10]);
+    }
+
+    #[test]
+    fn dropped_events_after_since_are_reported() {
+        let mut buffer = Buffer::default();
+        buffer.record("settings", "daemon-settings-changed", &json!({}), None);
+        for i in 0..PER_TYPE as u64 + 4 {
+            buffer.record("tick", "daemon-event", &json!(i), None);
+        }
+        // Ticks 2..=5 fell out of the ring; the settings event is still held
+        let page = buffer.recent(Some(0), None);
+        assert_eq!(page.events[0].seq, 1);
+        assert_eq!(page.events[1].seq, 6);
+        assert!(page.gap);
+        assert!(buffer.recent(Some(4), None).gap);
+        assert!(!buffer.recent(Some(5), None).gap);
+    }
+
+    #[test]
+    fn evicted_types_and_clear_are_reported() {
+        let mut buffer = Buffer::default();
+        for i in 0..MAX_TYPES + 1 {
+            buffer.record(&format!("t{}", i), "daemon-event", &json!(i), None);
+        }
+        // `t0` (seq 1) was evicted to make room for the last type
+        assert!(buffer.recent(Some(0), None).gap);
+        assert!(!buffer.recent(Some(1), None).gap);
+
+        buffer.clear();
+        let after = buffer.last_seq;
+        assert!(buffer.recent(Some(after - 1), None).gap);
+        assert!(!buffer.recent(Some(after), None).gap);
+        assert_eq!(buffer.record("x", "daemon-event", &json!(null), None), after + 1);
+    }
+}
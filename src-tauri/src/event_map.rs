@@ -0,0 +1,132 @@
+//! Which Tauri event each daemon SSE event type is forwarded as.
+//!
+//! Built-in entries cover the events the shell itself listens for. The file
+//! `~/.hecate/config/web-event-map.json` adds to or overrides them, and is
+//! reloaded by the config watcher when it changes:
+//!
+//! ```json
+//! { "version": 1, "events": { "venture_created": "daemon-venture-created", "tick": null } }
+//! ```
+//!
+//! A `null` entry silences that event type. Any type without an entry is
+//! forwarded as `daemon-event` with a `{ "type": ..., "data": ... }`
+//! envelope, so the frontend can subscribe to anything the daemon emits
+//! without a new hecate-web release.
+
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{OnceLock, RwLock};
+
+use crate::config_watcher;
+
+pub const EVENT_MAP_FILE: &str = "web-event-map.json";
+/// Tauri event carrying every unmapped daemon event.
+pub const GENERIC_EVENT: &str = "daemon-event";
+const MAP_VERSION: u32 = 1;
+
+const BUILT_IN: &[(&str, &str)] = &[
+    ("realm_join_status", "daemon-realm-join-status"),
+    ("identity_changed", "daemon-identity-changed"),
+    ("settings_changed", "daemon-settings-changed"),
+];
+
+/// How one SSE event type is forwarded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Forward {
+    /// Emitted under its own Tauri event name, payload as sent.
+    Named(String),
+    /// Emitted as `daemon-event` inside a type/data envelope.
+    Generic,
+    /// Not forwarded at all.
+    Silenced,
+}
+
+#[derive(Deserialize)]
+struct MapFile {
+    version: u32,
+    #[serde(default)]
+    events: HashMap<String, Option<String>>,
+}
+
+/// Event type -> Tauri event name, or `None` when silenced.
+type EventMap = HashMap<String, Option<String>>;
+
+static MAP: OnceLock<RwLock<EventMap>> = OnceLock::new();
+
+fn map_path() -> PathBuf {
+    config_watcher::config_dir().join(EVENT_MAP_FILE)
+}
+
+fn built_in() -> EventMap {
+    BUILT_IN
+        .iter()
+        .map(|(event_type, name)| (event_type.to_string(), Some(name.to_string())))
+        .collect()
+}
+
+/// Load the map file over the built-in entries.
+fn load() -> EventMap {
+    let mut map = built_in();
+    let path = map_path();
+    let content = match std::fs::read_to_string(&path) {
+        Ok(c) => c,
+        Err(_) => return map,
+    };
+    match serde_json::from_str::<MapFile>(&content) {
+        Ok(file) if file.version == MAP_VERSION => {
+            for (event_type, name) in file.events {
+                match name {
+                    Some(name) if !valid_event_name(&name) => {
+                        eprintln!("[event-map] ignoring {}: invalid Tauri event name {:?}", event_type, name);
+                    }
+                    name => {
+                        map.insert(event_type, name);
+                    }
+                }
+            }
+            eprintln!("[event-map] loaded {}", path.display());
+        }
+        Ok(file) => {
+            eprintln!(
+                "[event-map] unsupported version {} in {}, using built-in map",
+                file.version,
+                path.display()
+            );
+        }
+        Err(e) => {
+            eprintln!("[event-map] invalid {}: {}, using built-in map", path.display(), e);
+        }
+    }
+    map
+}
+
+fn map() -> &'static RwLock<EventMap> {
+    MAP.get_or_init(|| RwLock::new(load()))
+}
+
+/// Re-read the map file. Called by the config watcher on change.
+pub fn reload() {
+    let fresh = load();
+    if let Ok(mut current) = map().write() {
+        *current = fresh;
+    }
+}
+
+/// How to forward an SSE event of `event_type`.
+pub fn forward(event_type: &str) -> Forward {
+    let entry = map().read().ok().and_then(|m| m.get(event_type).cloned());
+    match entry {
+        Some(Some(name)) => Forward::Named(name),
+        Some(None) => Forward::Silenced,
+        None => Forward::Generic,
+    }
+}
+
+/// Tauri accepts alphanumerics, `-`, `/`, `:` and `_` in event names.
+pub fn valid_event_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '/' | ':' | '_'))
+}
@@ -0,0 +1,548 @@
+//! Opt-in HAR recording and replay of daemon traffic.
+//!
+//! While recording, every `hecate://` proxy exchange and every SSE stream
+//! (daemon and plugin) is captured: method, URL, headers, bodies, status and
+//! timings. Entries are updated in place as bodies stream in, so an export
+//! taken while an SSE stream is still open includes what it has received so
+//! far. Bodies are capped at [`MAX_BODY_CAPTURE`] bytes per entry, and only
+//! the newest [`MAX_ENTRIES`] entries are kept.
+//!
+//! In replay mode the proxy answers from a loaded HAR file instead of the
+//! daemon, so a bug can be reproduced without any daemon running. Requests
+//! match on method + URL; repeated requests walk through the recorded
+//! responses in order and then keep returning the last one. Replayed
+//! requests still go through the access policy and plugin isolation.
+//! Replayed responses carry no recorded `Content-Length` (the body may not
+//! be the one recorded in full); an entry whose body was cut off at the
+//! capture cap is served as far as it goes, marked with
+//! [`TRUNCATED_HEADER`].
+//!
+//! HAR files are read and written only in `~/.hecate/hecate-web/har/`; the
+//! commands take a file name, never a path.
+
+use base64::Engine;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tauri::http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode};
+
+use crate::config_watcher;
+
+const MAX_BODY_CAPTURE: usize = 1024 * 1024;
+/// Entries kept while recording; the oldest go first.
+const MAX_ENTRIES: usize = 500;
+const HAR_DIR: &str = "har";
+const MAX_FILE_NAME_LEN: usize = 128;
+/// Set on a replayed response whose recorded body was truncated.
+pub const TRUNCATED_HEADER: &str = "x-hecate-replay-truncated";
+/// Recorded response headers that describe the recorded framing, not the
+/// body being replayed.
+const FRAMING_HEADERS: &[&str] = &["content-length", "transfer-encoding"];
+
+struct Entry {
+    started: SystemTime,
+    started_at: Instant,
+    method: String,
+    url: String,
+    request_headers: Vec<(String, String)>,
+    request_body: Vec<u8>,
+    status: u16,
+    response_headers: Vec<(String, String)>,
+    response_body: Vec<u8>,
+    truncated: bool,
+    /// Milliseconds from sending the request until the response head arrived.
+    wait_ms: Option<f64>,
+    /// Milliseconds from start until the body finished (None while open).
+    total_ms: Option<f64>,
+    error: Option<String>,
+}
+
+struct ReplayEntry {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    truncated: bool,
+}
+
+#[derive(Default)]
+struct Recorder {
+    recording: bool,
+    next_id: u64,
+    entries: BTreeMap<u64, Entry>,
+    /// Recorded responses keyed by "METHOD url", plus a cursor per key.
+    replay: Option<HashMap<String, (Vec<ReplayEntry>, usize)>>,
+}
+
+static RECORDER: Mutex<Option<Recorder>> = Mutex::new(None);
+
+fn with_recorder<T>(f: impl FnOnce(&mut Recorder) -> T) -> T {
+    let mut guard = RECORDER.lock().unwrap_or_else(|e| e.into_inner());
+    f(guard.get_or_insert_with(Recorder::default))
+}
+
+#[derive(Serialize)]
+pub struct RecorderStatus {
+    pub recording: bool,
+    pub entries: usize,
+    pub replaying: bool,
+    pub replay_entries: usize,
+}
+
+/// Handle to one in-flight exchange being recorded. The entry is closed
+/// (its total time fixed) when the handle is dropped.
+pub struct Capture {
+    id: u64,
+}
+
+/// Start capturing an exchange, if recording is on.
+pub fn begin(method: &Method, url: &str, headers: &HeaderMap, body: &[u8]) -> Option<Capture> {
+    with_recorder(|r| {
+        if !r.recording {
+            return None;
+        }
+        let id = r.next_id;
+        r.next_id += 1;
+        while r.entries.len() >= MAX_ENTRIES {
+            r.entries.pop_first();
+        }
+        r.entries.insert(
+            id,
+            Entry {
+                started: SystemTime::now(),
+                started_at: Instant::now(),
+                method: method.to_string(),
+                url: url.to_string(),
+                request_headers: header_pairs(headers),
+                request_body: body[..body.len().min(MAX_BODY_CAPTURE)].to_vec(),
+                status: 0,
+                response_headers: Vec::new(),
+                response_body: Vec::new(),
+                truncated: body.len() > MAX_BODY_CAPTURE,
+                wait_ms: None,
+                total_ms: None,
+                error: None,
+            },
+        );
+        Some(Capture { id })
+    })
+}
+
+impl Capture {
+    fn update(&self, f: impl FnOnce(&mut Entry)) {
+        with_recorder(|r| {
+            if let Some(entry) = r.entries.get_mut(&self.id) {
+                f(entry);
+            }
+        });
+    }
+
+    /// Record the response status line and headers.
+    pub fn response(&self, status: StatusCode, headers: &HeaderMap) {
+        self.update(|e| {
+            e.status = status.as_u16();
+            e.response_headers = header_pairs(headers);
+            e.wait_ms = Some(elapsed_ms(e.started_at));
+        });
+    }
+
+    /// Append a chunk of response body.
+    pub fn body(&self, chunk: &[u8]) {
+        self.update(|e| {
+            let room = MAX_BODY_CAPTURE.saturating_sub(e.response_body.len());
+            if chunk.len() > room {
+                e.truncated = true;
+            }
+            e.response_body.extend_from_slice(&chunk[..chunk.len().min(room)]);
+        });
+    }
+
+    /// Record a transport failure for this exchange.
+    pub fn fail(&self, error: &str) {
+        self.update(|e| e.error = Some(error.to_string()));
+    }
+}
+
+impl Drop for Capture {
+    fn drop(&mut self) {
+        self.update(|e| e.total_ms = Some(elapsed_ms(e.started_at)));
+    }
+}
+
+/// Recorded response for a request, when replay mode is on.
+pub fn replay(method: &Method, url: &str) -> Option<(StatusCode, HeaderMap, Vec<u8>)> {
+    with_recorder(|r| {
+        let replay = r.replay.as_mut()?;
+        let (responses, cursor) = replay.get_mut(&replay_key(method.as_str(), url))?;
+        let entry = responses.get(*cursor).or_else(|| responses.last())?;
+        if *cursor < responses.len() {
+            *cursor += 1;
+        }
+        let mut headers = HeaderMap::new();
+        for (name, value) in &entry.headers {
+            if FRAMING_HEADERS.iter().any(|h| name.eq_ignore_ascii_case(h)) {
+                continue;
+            }
+            if let (Ok(n), Ok(v)) = (HeaderName::from_bytes(name.as_bytes()), HeaderValue::from_str(value)) {
+                headers.append(n, v);
+            }
+        }
+        if entry.truncated {
+            headers.insert(TRUNCATED_HEADER, HeaderValue::from_static("1"));
+        }
+        let status = StatusCode::from_u16(entry.status).unwrap_or(StatusCode::OK);
+        Some((status, headers, entry.body.clone()))
+    })
+}
+
+/// Whether replay mode is on (unmatched requests must not reach the daemon).
+pub fn is_replaying() -> bool {
+    with_recorder(|r| r.replay.is_some())
+}
+
+#[tauri::command]
+pub fn start_recording() {
+    with_recorder(|r| {
+        r.entries.clear();
+        r.recording = true;
+    });
+    eprintln!("[har] recording started");
+}
+
+/// Stop recording; returns the number of captured entries.
+#[tauri::command]
+pub fn stop_recording() -> usize {
+    let count = with_recorder(|r| {
+        r.recording = false;
+        r.entries.len()
+    });
+    eprintln!("[har] recording stopped ({} entries)", count);
+    count
+}
+
+/// Write the captured traffic as a HAR 1.2 file named `name` in the HAR
+/// directory; returns the entry count.
+#[tauri::command]
+pub fn export_har(name: String) -> Result<usize, String> {
+    let path = har_path(&name)?;
+    let (har, count) = with_recorder(|r| {
+        let entries: Vec<serde_json::Value> = r.entries.values().map(har_entry).collect();
+        let count = entries.len();
+        let har = serde_json::json!({
+            "log": {
+                "version": "1.2",
+                "creator": { "name": "hecate-web", "version": env!("CARGO_PKG_VERSION") },
+                "entries": entries,
+            }
+        });
+        (har, count)
+    });
+    let content = serde_json::to_vec_pretty(&har).map_err(|e| e.to_string())?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    std::fs::write(&path, content).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    eprintln!("[har] exported {} entries to {}", count, path.display());
+    Ok(count)
+}
+
+/// Load the HAR file named `name` from the HAR directory and serve its
+/// responses instead of the daemon.
+#[tauri::command]
+pub fn start_replay(name: String) -> Result<usize, String> {
+    let path = har_path(&name)?;
+    let content = std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let har: serde_json::Value = serde_json::from_slice(&content).map_err(|e| e.to_string())?;
+    let entries = har["log"]["entries"]
+        .as_array()
+        .ok_or("HAR file has no log.entries")?;
+
+    let mut replay: HashMap<String, (Vec<ReplayEntry>, usize)> = HashMap::new();
+    let mut count = 0;
+    for entry in entries {
+        let (Some(method), Some(url)) = (
+            entry["request"]["method"].as_str(),
+            entry["request"]["url"].as_str(),
+        ) else {
+            continue;
+        };
+        let response = &entry["response"];
+        let content = &response["content"];
+        let text = content["text"].as_str().unwrap_or("");
+        let body = if content["encoding"].as_str() == Some("base64") {
+            base64::engine::general_purpose::STANDARD
+                .decode(text)
+                .map_err(|e| format!("bad base64 body for {}: {}", url, e))?
+        } else {
+            text.as_bytes().to_vec()
+        };
+        let headers = response["headers"]
+            .as_array()
+            .map(|hs| {
+                hs.iter()
+                    .filter_map(|h| Some((h["name"].as_str()?.to_string(), h["value"].as_str()?.to_string())))
+                    .collect()
+            })
+            .unwrap_or_default();
+        replay
+            .entry(replay_key(method, url))
+            .or_insert_with(|| (Vec::new(), 0))
+            .0
+            .push(ReplayEntry {
+                status: response["status"].as_u64().unwrap_or(200) as u16,
+                headers,
+                body,
+                truncated: entry["_truncated"].as_bool().unwrap_or(false),
+            });
+        count += 1;
+    }
+
+    with_recorder(|r| r.replay = Some(replay));
+    eprintln!("[har] replaying {} entries from {}", count, path.display());
+    Ok(count)
+}
+
+#[tauri::command]
+pub fn stop_replay() {
+    with_recorder(|r| r.replay = None);
+    eprintln!("[har] replay stopped");
+}
+
+#[tauri::command]
+pub fn get_recorder_status() -> RecorderStatus {
+    with_recorder(|r| RecorderStatus {
+        recording: r.recording,
+        entries: r.entries.len(),
+        replaying: r.replay.is_some(),
+        replay_entries: r
+            .replay
+            .as_ref()
+            .map(|m| m.values().map(|(v, _)| v.len()).sum())
+            .unwrap_or(0),
+    })
+}
+
+/// `{state_dir}/har/{name}`, for a plain `*.har` file name.
+fn har_path(name: &str) -> Result<PathBuf, String> {
+    let valid = name.len() <= MAX_FILE_NAME_LEN
+        && name.ends_with(".har")
+        && !name.starts_with('.')
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'.' | b'-' | b'_'));
+    if !valid {
+        return Err(format!("invalid HAR file name {:?} (expected e.g. session-1.har)", name));
+    }
+    Ok(config_watcher::state_dir().join(HAR_DIR).join(name))
+}
+
+fn replay_key(method: &str, url: &str) -> String {
+    format!("{} {}", method.to_ascii_uppercase(), url)
+}
+
+fn header_pairs(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(k, v)| (k.as_str().to_string(), String::from_utf8_lossy(v.as_bytes()).to_string()))
+        .collect()
+}
+
+fn elapsed_ms(since: Instant) -> f64 {
+    since.elapsed().as_secs_f64() * 1000.0
+}
+
+fn har_headers(headers: &[(String, String)]) -> Vec<serde_json::Value> {
+    headers
+        .iter()
+        .map(|(name, value)| serde_json::json!({ "name": name, "value": value }))
+        .collect()
+}
+
+fn header_value<'a>(headers: &'a [(String, String)], name: &str) -> &'a str {
+    headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+        .unwrap_or("")
+}
+
+/// HAR `content`-style text: UTF-8 as-is, anything else base64.
+fn har_text(body: &[u8]) -> (String, Option<&'static str>) {
+    match std::str::from_utf8(body) {
+        Ok(text) => (text.to_string(), None),
+        Err(_) => (base64::engine::general_purpose::STANDARD.encode(body), Some("base64")),
+    }
+}
+
+fn har_entry(e: &Entry) -> serde_json::Value {
+    let query = e.url.split_once('?').map(|(_, q)| q).unwrap_or("");
+    let query_string: Vec<serde_json::Value> = query
+        .split('&')
+        .filter(|p| !p.is_empty())
+        .map(|p| {
+            let (name, value) = p.split_once('=').unwrap_or((p, ""));
+            serde_json::json!({ "name": name, "value": value })
+        })
+        .collect();
+
+    let total = e.total_ms.unwrap_or_else(|| elapsed_ms(e.started_at));
+    let wait = e.wait_ms.unwrap_or(total);
+
+    let (response_text, response_encoding) = har_text(&e.response_body);
+    let mut content = serde_json::json!({
+        "size": e.response_body.len(),
+        "mimeType": header_value(&e.response_headers, "content-type"),
+        "text": response_text,
+    });
+    if let Some(enc) = response_encoding {
+        content["encoding"] = enc.into();
+    }
+
+    let mut request = serde_json::json!({
+        "method": e.method,
+        "url": e.url,
+        "httpVersion": "HTTP/1.1",
+        "cookies": [],
+        "headers": har_headers(&e.request_headers),
+        "queryString": query_string,
+        "headersSize": -1,
+        "bodySize": e.request_body.len(),
+    });
+    if !e.request_body.is_empty() {
+        let (text, encoding) = har_text(&e.request_body);
+        request["postData"] = serde_json::json!({
+            "mimeType": header_value(&e.request_headers, "content-type"),
+            "text": text,
+        });
+        if let Some(enc) = encoding {
+            request["postData"]["encoding"] = enc.into();
+        }
+    }
+
+    let mut entry = serde_json::json!({
+        "startedDateTime": iso8601(e.started),
+        "time": total,
+        "request": request,
+        "response": {
+            "status": e.status,
+            "statusText": StatusCode::from_u16(e.status).ok().and_then(|s| s.canonical_reason()).unwrap_or(""),
+            "httpVersion": "HTTP/1.1",
+            "cookies": [],
+            "headers": har_headers(&e.response_headers),
+            "content": content,
+            "redirectURL": header_value(&e.response_headers, "location"),
+            "headersSize": -1,
+            "bodySize": e.response_body.len(),
+        },
+        "cache": {},
+        "timings": {
+            "blocked": -1,
+            "dns": -1,
+            "connect": -1,
+            "send": 0,
+            "wait": wait,
+            "receive": (total - wait).max(0.0),
+        },
+    });
+    if e.truncated {
+        entry["_truncated"] = true.into();
+    }
+    if e.total_ms.is_none() {
+        entry["_inProgress"] = true.into();
+    }
+    if let Some(err) = &e.error {
+        entry["_error"] = err.as_str().into();
+    }
+    entry
+}
+
+/// Format a timestamp as ISO 8601 UTC with millisecond precision.
+fn iso8601(t: SystemTime) -> String {
+    let d = t.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = d.as_secs();
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let rem = secs % 86_400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60,
+        d.subsec_millis()
+    )
+}
+
+/// Days since 1970-01-01 to (year, month, day), proleptic Gregorian.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn har_files_stay_in_the_har_dir() {
+        config_watcher::test_home();
+        let dir = config_watcher::state_dir().join(HAR_DIR);
+        assert_eq!(har_path("session-1.har").unwrap(), dir.join("session-1.har"));
+        for name in ["", ".har", "../x.har", "/etc/x.har", "a/b.har", "a\\b.har", "x.json", "x.har/"] {
+            assert!(har_path(name).is_err(), "{:?}", name);
+        }
+        assert!(har_path(&format!("{}.har", "a".repeat(MAX_FILE_NAME_LEN))).is_err());
+    }
+
+    fn record(url: &str, headers: &[(&'static str, String)], body: &[u8]) {
+        let mut response_headers = HeaderMap::new();
+        for (name, value) in headers {
+            response_headers.insert(*name, HeaderValue::from_str(value).unwrap());
+        }
+        let capture = begin(&Method::GET, url, &HeaderMap::new(), &[]).unwrap();
+        capture.response(StatusCode::OK, &response_headers);
+        capture.body(body);
+    }
+
+    #[test]
+    fn exported_traffic_replays_without_recorded_length() {
+        config_watcher::test_home();
+        start_recording();
+        record(
+            "hecate://localhost/api/small",
+            &[("content-type", "application/json".into()), ("content-length", "2".into())],
+            b"{}",
+        );
+        let big = vec![b'x'; MAX_BODY_CAPTURE + 10];
+        record("hecate://localhost/api/big", &[("content-length", big.len().to_string())], &big);
+        stop_recording();
+
+        assert_eq!(export_har("round-trip.har".into()), Ok(2));
+        assert_eq!(start_replay("round-trip.har".into()), Ok(2));
+
+        let (status, headers, body) = replay(&Method::GET, "hecate://localhost/api/small").unwrap();
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, b"{}");
+        assert_eq!(headers["content-type"], "application/json");
+        assert!(headers.get("content-length").is_none());
+        assert!(headers.get(TRUNCATED_HEADER).is_none());
+
+        // Served as far as it was captured, flagged, and with no length
+        // promising the rest
+        let (_, headers, body) = replay(&Method::GET, "hecate://localhost/api/big").unwrap();
+        assert_eq!(body.len(), MAX_BODY_CAPTURE);
+        assert!(headers.get("content-length").is_none());
+        assert_eq!(headers[TRUNCATED_HEADER], "1");
+        stop_replay();
+    }
+}
@@ -1,20 +1,43 @@
 mod app_updater;
 mod config_watcher;
+mod correlation;
+mod daemon_client;
+mod daemon_profiles;
 mod daemon_streaming;
 mod daemon_watcher;
+mod event_buffer;
+mod event_map;
+mod har_recorder;
+#[cfg(debug_assertions)]
+mod mock_daemon;
+mod offline_queue;
 mod plugin_discovery;
+mod plugin_frame;
+mod plugin_isolation;
+mod plugin_name;
 mod plugin_streaming;
 mod plugin_updater;
 mod plugin_watcher;
+mod proxy_error;
+mod proxy_limits;
+mod proxy_policy;
+mod response_cache;
+mod session_identity;
 mod socket_proxy;
+mod sse;
 mod traffic;
+mod transport;
 mod webview_opener;
+mod ws_tunnel;
 
 use tauri::http::Response;
 use tauri::Manager;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    #[cfg(debug_assertions)]
+    mock_daemon::start_if_requested();
+
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_process::init())
@@ -40,17 +63,44 @@ pub fn run() {
             Ok(())
         })
         .register_asynchronous_uri_scheme_protocol("hecate", |_ctx, request, responder| {
-            std::thread::spawn(move || {
-                let response = match socket_proxy::proxy_request(&request) {
-                    Ok(resp) => resp,
+            // Requests run as tasks on the shared async runtime rather than a
+            // thread each; `proxy_limits` bounds how many reach each socket.
+            tauri::async_runtime::spawn(async move {
+                let mut request = request;
+                let ids = correlation::ensure(request.headers_mut());
+                let method = request.method().clone();
+                let path = request.uri().path().to_string();
+                let response = match socket_proxy::proxy_request(request).await {
+                    Ok(resp) => {
+                        // Not streamed to the webview: the responder only
+                        // takes a complete body, so the whole response is held
+                        // in memory before the webview sees any of it and
+                        // memory grows with the response. The socket is still
+                        // read chunk by chunk (cache, HAR and traffic counters
+                        // follow along), and Range requests are capped by the
+                        // proxy so media arrives a piece at a time; any other
+                        // large download is buffered whole.
+                        let (parts, body) = resp.into_parts();
+                        let mut buf = Vec::with_capacity(body.size_hint().unwrap_or(0));
+                        match body.for_each_chunk(|chunk| buf.extend_from_slice(chunk)).await {
+                            Ok(()) => Response::from_parts(parts, buf),
+                            Err(e) => {
+                                // Never hand out a truncated body as a success
+                                eprintln!(
+                                    "[socket_proxy] {} {} {}: body stream aborted: {}",
+                                    ids.request_id, method, path, e
+                                );
+                                let mut response = e.into_response();
+                                ids.apply(response.headers_mut());
+                                response
+                            }
+                        }
+                    }
                     Err(e) => {
-                        let body = format!(r#"{{"ok":false,"error":"{}"}}"#, e);
-                        Response::builder()
-                            .status(503)
-                            .header("Content-Type", "application/json")
-                            .header("Access-Control-Allow-Origin", "*")
-                            .body(body.into_bytes())
-                            .unwrap()
+                        eprintln!("[socket_proxy] {} {} {}: {}", ids.request_id, method, path, e);
+                        let mut response = e.into_response();
+                        ids.apply(response.headers_mut());
+                        response
                     }
                 };
                 responder.respond(response);
@@ -61,6 +111,18 @@ pub fn run() {
             app_updater::install_app_update,
             socket_proxy::check_daemon_health,
             daemon_watcher::get_cached_health,
+            daemon_streaming::get_daemon_stream_state,
+            event_buffer::get_recent_daemon_events,
+            daemon_profiles::list_daemon_profiles,
+            daemon_profiles::switch_daemon_profile,
+            har_recorder::start_recording,
+            har_recorder::stop_recording,
+            har_recorder::export_har,
+            har_recorder::start_replay,
+            har_recorder::stop_replay,
+            har_recorder::get_recorder_status,
+            offline_queue::list_offline_queue,
+            offline_queue::cancel_queued_request,
             plugin_discovery::discover_plugins,
             plugin_updater::check_plugin_updates,
             plugin_updater::install_plugin_update,
@@ -68,7 +130,16 @@ pub fn run() {
             traffic::get_traffic_counters,
             webview_opener::open_webview,
             webview_opener::close_webview,
+            ws_tunnel::ws_open,
+            ws_tunnel::ws_send,
+            ws_tunnel::ws_close,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|_app, _event| {
+            #[cfg(debug_assertions)]
+            if let tauri::RunEvent::Exit = _event {
+                mock_daemon::stop();
+            }
+        });
 }
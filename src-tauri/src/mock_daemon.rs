@@ -0,0 +1,406 @@
+//! Fixture-driven stand-ins for hecate-daemon and the plugin daemons, for
+//! working on the frontend without any real services.
+//!
+//! Debug builds only. Enabled with `--mock-daemon[=DIR]` on the command
+//! line or `HECATE_MOCK_DAEMON=DIR` in the environment (`1` or an empty
+//! value picks the sample fixtures: the first `fixtures/mock-daemon` found
+//! above the working directory or the executable). Before anything resolves
+//! a path, a fresh hecate tree is created under the temp dir and made the
+//! process's hecate home (see `config_watcher::override_hecate_home`), and
+//! in-process HTTP servers are bound on the usual socket paths inside it, so
+//! the proxy, watchers and streams find them exactly as they would real
+//! daemons. The tree is deleted on exit, and trees left behind by runs that
+//! did not exit cleanly are swept up on the next start.
+//!
+//! Fixture layout:
+//!
+//! ```text
+//! DIR/daemon/                    the main daemon
+//!     api/settings.json          GET /api/settings
+//!     api/realms/join/initiate.POST.json
+//!                                POST /api/realms/join/initiate
+//!     api/events.sse             GET /api/events, a scripted SSE stream
+//! DIR/plugins/{name}/            one plugin daemon per directory
+//!     manifest.json              GET /manifest
+//!     ui/component.js            any other file is served as-is
+//! ```
+//!
+//! A request is answered from `{path}.{METHOD}.json`, then (for GET) from
+//! `{path}.sse`, `{path}.json` or the file at `{path}` itself. Without a
+//! fixture, `/health` and `/manifest` get a built-in answer, writes get
+//! `{"ok": true}` and reads get 404.
+//!
+//! `.sse` files are sent as written, one event at a time. A `: sleep <ms>`
+//! comment line pauses the script; once it ends the stream stays open with
+//! keepalive comments, as the daemon's does.
+
+use bytes::Bytes;
+use http_body_util::combinators::BoxBody;
+use http_body_util::{BodyExt, Full, StreamBody};
+use hyper::body::{Frame, Incoming};
+use hyper::header::{CACHE_CONTROL, CONTENT_TYPE};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use std::convert::Infallible;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::net::UnixListener;
+use tokio::sync::mpsc;
+
+use crate::config_watcher;
+use crate::plugin_name::PluginName;
+
+const FLAG: &str = "--mock-daemon";
+const ENV_VAR: &str = "HECATE_MOCK_DAEMON";
+/// Where the sample fixtures live, relative to the repository root.
+const SAMPLE_FIXTURES: &str = "fixtures/mock-daemon";
+/// Mock trees are `{temp_dir}/hecate-web-mock-{pid}`.
+const HOME_PREFIX: &str = "hecate-web-mock-";
+const SOCKET_NAME: &str = "api.sock";
+const KEEPALIVE: Duration = Duration::from_secs(15);
+
+type MockBody = BoxBody<Bytes, Infallible>;
+
+static STARTED: OnceLock<Instant> = OnceLock::new();
+/// The mock tree of this process, removed again by [`stop`].
+static HOME: OnceLock<PathBuf> = OnceLock::new();
+
+/// One mocked daemon: its name for logs and its fixture directory.
+struct Root {
+    name: String,
+    dir: PathBuf,
+    plugin: Option<PluginName>,
+}
+
+enum Fixture {
+    File(PathBuf),
+    Sse(PathBuf),
+}
+
+/// Start the mock daemons if asked to on the command line or in the
+/// environment. Must run before anything resolves socket paths.
+pub fn start_if_requested() {
+    let Some(fixtures) = requested_fixtures() else {
+        return;
+    };
+    match start(&fixtures) {
+        Ok(home) => eprintln!(
+            "[mock-daemon] serving {} with hecate home {}",
+            fixtures.display(),
+            home.display()
+        ),
+        Err(e) => {
+            // Carrying on would quietly talk to a real daemon instead
+            eprintln!("[mock-daemon] could not start from {}: {}", fixtures.display(), e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn requested_fixtures() -> Option<PathBuf> {
+    let from_args = std::env::args().skip(1).find_map(|arg| {
+        if arg == FLAG {
+            Some(String::new())
+        } else {
+            arg.strip_prefix(FLAG).and_then(|v| v.strip_prefix('=')).map(str::to_string)
+        }
+    });
+    let value = from_args.or_else(|| std::env::var(ENV_VAR).ok())?;
+    match value.as_str() {
+        "" | "1" | "true" => Some(sample_fixtures()),
+        "0" | "false" => None,
+        dir => Some(PathBuf::from(dir)),
+    }
+}
+
+/// The sample fixtures in the source tree this build is being run from.
+fn sample_fixtures() -> PathBuf {
+    let cwd = std::env::current_dir().ok();
+    let exe = std::env::current_exe().ok();
+    cwd.iter()
+        .chain(exe.iter())
+        .flat_map(|p| p.ancestors())
+        .map(|dir| dir.join(SAMPLE_FIXTURES))
+        .find(|dir| dir.is_dir())
+        .unwrap_or_else(|| PathBuf::from(SAMPLE_FIXTURES))
+}
+
+/// Delete this process's mock tree. Called when the app exits.
+pub fn stop() {
+    if let Some(home) = HOME.get() {
+        match std::fs::remove_dir_all(home) {
+            Ok(()) => eprintln!("[mock-daemon] removed {}", home.display()),
+            Err(e) => eprintln!("[mock-daemon] could not remove {}: {}", home.display(), e),
+        }
+    }
+}
+
+/// Remove mock trees left by earlier runs: any whose daemon socket no
+/// longer answers.
+fn remove_stale_homes() {
+    let Ok(entries) = std::fs::read_dir(std::env::temp_dir()) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if !entry.file_name().to_string_lossy().starts_with(HOME_PREFIX) {
+            continue;
+        }
+        let socket = daemon_socket(&entry.path());
+        if std::os::unix::net::UnixStream::connect(&socket).is_err() {
+            eprintln!("[mock-daemon] removing stale {}", entry.path().display());
+            let _ = std::fs::remove_dir_all(entry.path());
+        }
+    }
+}
+
+fn daemon_socket(home: &Path) -> PathBuf {
+    home.join("hecate-daemon").join("sockets").join(SOCKET_NAME)
+}
+
+fn start(fixtures: &Path) -> std::io::Result<PathBuf> {
+    if !fixtures.is_dir() {
+        return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "no such fixture directory"));
+    }
+    STARTED.get_or_init(Instant::now);
+    remove_stale_homes();
+
+    let home = std::env::temp_dir().join(format!("{}{}", HOME_PREFIX, std::process::id()));
+    let _ = std::fs::remove_dir_all(&home);
+    std::fs::create_dir_all(&home)?;
+    let _ = HOME.set(home.clone());
+
+    // Every socket is bound before any is served, so the whole tree is in
+    // place by the time the watchers first look
+    let mut servers = Vec::new();
+    let daemon = Root {
+        name: "daemon".to_string(),
+        dir: fixtures.join("daemon"),
+        plugin: None,
+    };
+    servers.push((bind(&daemon_socket(&home), &daemon)?, daemon));
+
+    if let Ok(entries) = std::fs::read_dir(fixtures.join("plugins")) {
+        for entry in entries.flatten().filter(|e| e.path().is_dir()) {
+            let dir_name = entry.file_name().to_string_lossy().to_string();
+            let name = match PluginName::parse(&dir_name) {
+                Ok(name) => name,
+                Err(e) => {
+                    eprintln!("[mock-daemon] skipping plugins/{}: {}", dir_name, e);
+                    continue;
+                }
+            };
+            let socket = home.join(name.dir_name()).join("sockets").join(SOCKET_NAME);
+            let plugin = Root {
+                name: name.to_string(),
+                dir: entry.path(),
+                plugin: Some(name),
+            };
+            servers.push((bind(&socket, &plugin)?, plugin));
+        }
+    }
+
+    // Everything that looks for the hecate tree or the daemon socket now
+    // lands in the mock home
+    config_watcher::override_hecate_home(home.clone());
+    for (listener, root) in servers {
+        tauri::async_runtime::spawn(serve(listener, Arc::new(root)));
+    }
+    Ok(home)
+}
+
+/// Bind the socket for `root` (creating its directory), to be served once
+/// every socket is in place.
+fn bind(socket: &Path, root: &Root) -> std::io::Result<std::os::unix::net::UnixListener> {
+    if let Some(dir) = socket.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let listener = std::os::unix::net::UnixListener::bind(socket)?;
+    listener.set_nonblocking(true)?;
+    eprintln!("[mock-daemon] {} on {}", root.name, socket.display());
+    Ok(listener)
+}
+
+async fn serve(listener: std::os::unix::net::UnixListener, root: Arc<Root>) {
+    let listener = match UnixListener::from_std(listener) {
+        Ok(l) => l,
+        Err(e) => {
+            eprintln!("[mock-daemon] {}: cannot listen: {}", root.name, e);
+            return;
+        }
+    };
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                eprintln!("[mock-daemon] {}: accept failed: {}", root.name, e);
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+        let root = root.clone();
+        tauri::async_runtime::spawn(async move {
+            let service = service_fn(move |request| respond(root.clone(), request));
+            if let Err(e) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                eprintln!("[mock-daemon] connection error: {}", e);
+            }
+        });
+    }
+}
+
+async fn respond(root: Arc<Root>, request: Request<Incoming>) -> Result<Response<MockBody>, Infallible> {
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    // Read the body so the connection stays usable for the next request
+    let _ = request.into_body().collect().await;
+
+    let response = match lookup(&root.dir, &method, &path) {
+        Some(Fixture::Sse(file)) => sse(file),
+        Some(Fixture::File(file)) => match std::fs::read(&file) {
+            Ok(content) => with_body(StatusCode::OK, content_type(&file), content),
+            Err(e) => json(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                serde_json::json!({"ok": false, "error": "fixture_unreadable", "detail": e.to_string()}),
+            ),
+        },
+        None => fallback(&root, &method, &path),
+    };
+    eprintln!("[mock-daemon] {} {} {} -> {}", root.name, method, path, response.status().as_u16());
+    Ok(response)
+}
+
+/// Find the fixture for a request, without ever leaving the fixture dir.
+fn lookup(dir: &Path, method: &Method, path: &str) -> Option<Fixture> {
+    let rel = path.trim_start_matches('/');
+    if rel.split('/').any(|seg| seg == "." || seg == ".." || seg.contains('\\')) {
+        return None;
+    }
+    let base = dir.join(if rel.is_empty() { "index.html" } else { rel });
+    let file = |suffix: &str| {
+        let mut name = base.clone().into_os_string();
+        name.push(suffix);
+        Some(PathBuf::from(name)).filter(|p| p.is_file())
+    };
+
+    if let Some(f) = file(&format!(".{}.json", method)) {
+        return Some(Fixture::File(f));
+    }
+    if method != Method::GET {
+        return None;
+    }
+    if let Some(f) = file(".sse") {
+        return Some(Fixture::Sse(f));
+    }
+    file(".json").or_else(|| file("")).map(Fixture::File)
+}
+
+fn fallback(root: &Root, method: &Method, path: &str) -> Response<MockBody> {
+    match (method, path, &root.plugin) {
+        (&Method::GET, "/health", _) => json(
+            StatusCode::OK,
+            serde_json::json!({
+                "status": "healthy",
+                "ready": true,
+                "service": format!("{} (mock)", root.name),
+                "version": "mock",
+                "uptime_seconds": STARTED.get().map(|t| t.elapsed().as_secs()).unwrap_or(0),
+                "identity": "initialized",
+            }),
+        ),
+        (&Method::GET, "/manifest", Some(name)) => json(
+            StatusCode::OK,
+            serde_json::json!({
+                "name": name,
+                "version": "0.0.0-mock",
+                "icon": "🧩",
+                "description": format!("Mock {} plugin", name),
+                "tag": format!("{}-studio", name),
+            }),
+        ),
+        (&Method::GET, _, _) | (&Method::HEAD, _, _) => json(
+            StatusCode::NOT_FOUND,
+            serde_json::json!({"ok": false, "error": "no_fixture", "path": path}),
+        ),
+        _ => json(StatusCode::OK, serde_json::json!({"ok": true})),
+    }
+}
+
+/// Play an `.sse` script, then hold the stream open with keepalives until
+/// the client goes away.
+fn sse(file: PathBuf) -> Response<MockBody> {
+    let (tx, rx) = mpsc::channel::<Bytes>(16);
+    tauri::async_runtime::spawn(async move {
+        let script = std::fs::read_to_string(&file).unwrap_or_else(|e| {
+            eprintln!("[mock-daemon] cannot read {}: {}", file.display(), e);
+            String::new()
+        });
+        let mut pending = String::new();
+        for line in script.lines() {
+            if let Some(ms) = line.strip_prefix(": sleep ").and_then(|ms| ms.trim().parse().ok()) {
+                if !pending.is_empty() && tx.send(Bytes::from(std::mem::take(&mut pending))).await.is_err() {
+                    return;
+                }
+                tokio::time::sleep(Duration::from_millis(ms)).await;
+                continue;
+            }
+            pending.push_str(line);
+            pending.push('\n');
+            if line.is_empty() && tx.send(Bytes::from(std::mem::take(&mut pending))).await.is_err() {
+                return;
+            }
+        }
+        if !pending.is_empty() && tx.send(Bytes::from(pending)).await.is_err() {
+            return;
+        }
+        loop {
+            tokio::time::sleep(KEEPALIVE).await;
+            if tx.send(Bytes::from_static(b": keepalive\n\n")).await.is_err() {
+                return;
+            }
+        }
+    });
+
+    let frames = futures_util::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|chunk| (Ok(Frame::data(chunk)), rx))
+    });
+    let mut response = Response::new(StreamBody::new(frames).boxed());
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, "text/event-stream".parse().expect("valid header"));
+    response
+        .headers_mut()
+        .insert(CACHE_CONTROL, "no-cache".parse().expect("valid header"));
+    response
+}
+
+fn json(status: StatusCode, value: serde_json::Value) -> Response<MockBody> {
+    with_body(status, "application/json", value.to_string().into_bytes())
+}
+
+fn with_body(status: StatusCode, content_type: &str, content: Vec<u8>) -> Response<MockBody> {
+    let mut response = Response::new(Full::new(Bytes::from(content)).boxed());
+    *response.status_mut() = status;
+    if let Ok(value) = content_type.parse() {
+        response.headers_mut().insert(CONTENT_TYPE, value);
+    }
+    response
+}
+
+fn content_type(file: &Path) -> &'static str {
+    match file.extension().and_then(|e| e.to_str()).unwrap_or("") {
+        "json" => "application/json",
+        "html" => "text/html; charset=utf-8",
+        "js" | "mjs" => "text/javascript",
+        "css" => "text/css",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "wasm" => "application/wasm",
+        "txt" => "text/plain; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
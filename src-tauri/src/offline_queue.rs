@@ -0,0 +1,525 @@
+//! Write-behind queue for mutating `hecate://` requests made while the
+//! daemon is down.
+//!
+//! Opt-in per request: a POST/PUT/PATCH/DELETE to the daemon carrying
+//! `X-Hecate-Queue-If-Offline` is, when the daemon is unavailable, written to
+//! `~/.hecate/hecate-web/offline-queue/` (one `.json` metadata file and one
+//! `.body` file per request) and answered with `202 Accepted` and a queue id.
+//! Once `daemon_watcher` sees the daemon healthy again the queue is replayed
+//! through the proxy, oldest first, one request at a time. Each replayed
+//! request is reported as an `offline-queue-result` event.
+//!
+//! While anything is still queued, new opted-in requests join the back of
+//! the queue even if the daemon is up, so they cannot overtake older writes.
+//! Replay stops (keeping the rest) if the daemon goes away again, and picks
+//! up at the next healthy check. A busy daemon (`429`) only delays it.
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::http::{HeaderMap, Method, Request};
+use tauri::Emitter;
+
+use crate::config_watcher;
+use crate::daemon_client;
+use crate::proxy_error::ProxyError;
+use crate::socket_proxy;
+
+/// Request header that opts a write into the queue.
+pub const QUEUE_HEADER: &str = "x-hecate-queue-if-offline";
+const QUEUE_DIR: &str = "offline-queue";
+/// Times one request is retried while the daemon says it is too busy,
+/// before replay pauses until the next health check.
+const BUSY_RETRIES: u32 = 5;
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Item {
+    id: String,
+    seq: u64,
+    method: String,
+    /// Path and query as requested on `hecate://`, routed again on replay.
+    uri: String,
+    headers: Vec<(String, String)>,
+    queued_at: u64,
+}
+
+/// What the frontend sees for a queued request.
+#[derive(Serialize, Clone)]
+pub struct QueuedRequest {
+    pub id: String,
+    pub method: String,
+    pub path: String,
+    pub body_bytes: u64,
+    /// Unix time in milliseconds.
+    pub queued_at: u64,
+    /// Currently being sent to the daemon (can no longer be cancelled).
+    pub replaying: bool,
+}
+
+/// Outcome of replaying one queued request.
+#[derive(Serialize, Clone)]
+pub struct QueueResult {
+    pub id: String,
+    pub method: String,
+    pub path: String,
+    /// Daemon status code, or the proxy's error status.
+    pub status: u16,
+    pub ok: bool,
+    pub error: Option<String>,
+    /// Response body, if it is UTF-8.
+    pub body: Option<String>,
+}
+
+struct Queue {
+    items: VecDeque<Item>,
+    next_seq: u64,
+    /// Id of the request currently being replayed.
+    in_flight: Option<String>,
+}
+
+static QUEUE: Mutex<Option<Queue>> = Mutex::new(None);
+static REPLAYING: AtomicBool = AtomicBool::new(false);
+
+fn with_queue<T>(f: impl FnOnce(&mut Queue) -> T) -> T {
+    let mut guard = QUEUE.lock().unwrap_or_else(|e| e.into_inner());
+    f(guard.get_or_insert_with(load))
+}
+
+/// Tauri command: the queued requests, oldest first.
+#[tauri::command]
+pub fn list_offline_queue() -> Vec<QueuedRequest> {
+    with_queue(|queue| {
+        queue
+            .items
+            .iter()
+            .map(|item| QueuedRequest {
+                id: item.id.clone(),
+                method: item.method.clone(),
+                path: item.uri.clone(),
+                body_bytes: std::fs::metadata(item_path(&item.id, "body")).map(|m| m.len()).unwrap_or(0),
+                queued_at: item.queued_at,
+                replaying: queue.in_flight.as_deref() == Some(item.id.as_str()),
+            })
+            .collect()
+    })
+}
+
+/// Tauri command: drop a queued request before it is replayed.
+#[tauri::command]
+pub fn cancel_queued_request(id: String) -> Result<(), String> {
+    with_queue(|queue| {
+        if queue.in_flight.as_deref() == Some(id.as_str()) {
+            return Err(format!("{} is already being sent", id));
+        }
+        let pos = queue
+            .items
+            .iter()
+            .position(|item| item.id == id)
+            .ok_or_else(|| format!("no queued request {}", id))?;
+        queue.items.remove(pos);
+        remove_files(&id);
+        eprintln!("[offline-queue] cancelled {}", id);
+        Ok(())
+    })
+}
+
+/// Whether requests are waiting to be replayed.
+pub fn is_pending() -> bool {
+    with_queue(|queue| !queue.items.is_empty())
+}
+
+/// Persist a request for replay and return its queue id.
+pub fn enqueue(method: &Method, uri: &str, headers: &HeaderMap, body: &[u8]) -> std::io::Result<String> {
+    std::fs::create_dir_all(queue_dir())?;
+    let headers = headers
+        .iter()
+        .filter(|(name, _)| name.as_str() != QUEUE_HEADER)
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect();
+    with_queue(|queue| {
+        let queued_at = now_millis();
+        let item = Item {
+            id: format!("q-{}-{}", queued_at, queue.next_seq),
+            seq: queue.next_seq,
+            method: method.to_string(),
+            uri: uri.to_string(),
+            headers,
+            queued_at,
+        };
+        std::fs::write(item_path(&item.id, "body"), body)?;
+        if let Err(e) = serde_json::to_vec(&item).map_err(std::io::Error::other).and_then(|json| {
+            std::fs::write(item_path(&item.id, "json"), json)
+        }) {
+            remove_files(&item.id);
+            return Err(e);
+        }
+        eprintln!("[offline-queue] queued {} {} as {}", item.method, item.uri, item.id);
+        queue.next_seq += 1;
+        let id = item.id.clone();
+        queue.items.push_back(item);
+        Ok(id)
+    })
+}
+
+/// Body of the `202 Accepted` answer to a queued request.
+pub fn accepted_body(id: &str) -> Vec<u8> {
+    serde_json::json!({ "queued": true, "id": id }).to_string().into_bytes()
+}
+
+/// Replay the queue on a background thread. Called on every healthy daemon
+/// check; a no-op if the queue is empty or already replaying.
+pub fn resume(app: &tauri::AppHandle) {
+    if !is_pending() || REPLAYING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let app = app.clone();
+    std::thread::spawn(move || {
+        eprintln!("[offline-queue] daemon is healthy, replaying queue");
+        let mut paused = false;
+        let mut busy_retries = 0;
+        while let Some(item) = next_item() {
+            match settle(&item, replay(&item), &mut busy_retries) {
+                Step::Report(result) => {
+                    eprintln!("[offline-queue] {} {} {} -> {}", item.id, item.method, item.uri, result.status);
+                    if let Err(e) = app.emit("offline-queue-result", &result) {
+                        eprintln!("[offline-queue] emit failed: {}", e);
+                    }
+                }
+                Step::Retry(delay) => {
+                    eprintln!("[offline-queue] daemon busy, retrying {} in {:?}", item.id, delay);
+                    std::thread::sleep(delay);
+                }
+                Step::Pause => {
+                    eprintln!("[offline-queue] daemon unavailable, pausing replay at {}", item.id);
+                    paused = true;
+                    break;
+                }
+            }
+        }
+        REPLAYING.store(false, Ordering::SeqCst);
+        // Something may have been queued between the last pop and now
+        if !paused {
+            resume(&app);
+        }
+    });
+}
+
+fn next_item() -> Option<Item> {
+    with_queue(|queue| {
+        let item = queue.items.front().cloned()?;
+        queue.in_flight = Some(item.id.clone());
+        Some(item)
+    })
+}
+
+/// How replaying one queued request went.
+enum Replay {
+    /// The daemon (or the proxy) answered; the request leaves the queue.
+    Done(QueueResult),
+    /// The proxy refused it for now (too many requests in flight); try the
+    /// same request again after the delay.
+    Busy(Duration),
+    /// The daemon could not be reached; the request stays queued.
+    Unreachable,
+}
+
+/// What the replay loop does after one attempt.
+enum Step {
+    /// The request is out of the queue; report its result.
+    Report(QueueResult),
+    /// Send the same request again after the delay.
+    Retry(Duration),
+    /// Stop, keeping the rest for the next healthy check.
+    Pause,
+}
+
+/// Apply the outcome of replaying `item` to the queue. `busy_retries`
+/// counts the `429`s seen for the request at the front.
+fn settle(item: &Item, outcome: Replay, busy_retries: &mut u32) -> Step {
+    with_queue(|queue| {
+        queue.in_flight = None;
+        if let Replay::Done(_) = outcome {
+            queue.items.retain(|i| i.id != item.id);
+        }
+    });
+    match outcome {
+        Replay::Done(result) => {
+            *busy_retries = 0;
+            remove_files(&item.id);
+            Step::Report(result)
+        }
+        Replay::Busy(delay) if *busy_retries < BUSY_RETRIES => {
+            *busy_retries += 1;
+            Step::Retry(delay)
+        }
+        Replay::Busy(_) | Replay::Unreachable => Step::Pause,
+    }
+}
+
+/// Send one queued request through the proxy.
+fn replay(item: &Item) -> Replay {
+    let body = std::fs::read(item_path(&item.id, "body")).unwrap_or_default();
+    let mut builder = Request::builder()
+        .method(item.method.as_str())
+        .uri(format!("hecate://daemon{}", item.uri));
+    for (name, value) in &item.headers {
+        builder = builder.header(name, value);
+    }
+    let mut result = QueueResult {
+        id: item.id.clone(),
+        method: item.method.clone(),
+        path: item.uri.clone(),
+        status: 0,
+        ok: false,
+        error: None,
+        body: None,
+    };
+    let request = match builder.body(body) {
+        Ok(r) => r,
+        Err(e) => {
+            result.status = 400;
+            result.error = Some(e.to_string());
+            return Replay::Done(result);
+        }
+    };
+
+    daemon_client::block_on(async {
+        match socket_proxy::proxy_request(request).await {
+            Ok(response) => {
+                let (parts, body) = response.into_parts();
+                let mut buf = Vec::new();
+                if let Err(e) = body.for_each_chunk(|chunk| buf.extend_from_slice(chunk)).await {
+                    result.error = Some(e.to_string());
+                }
+                result.status = parts.status.as_u16();
+                result.ok = parts.status.is_success() && result.error.is_none();
+                result.body = String::from_utf8(buf).ok();
+                Replay::Done(result)
+            }
+            Err(e @ ProxyError::TooManyRequests(_)) => {
+                Replay::Busy(Duration::from_secs(e.retry_after().unwrap_or(1).into()))
+            }
+            // Never reached the daemon: keep it for the next healthy spell
+            Err(e) if e.retry_after().is_some() => Replay::Unreachable,
+            Err(e) => {
+                result.status = e.status();
+                result.error = Some(e.to_string());
+                Replay::Done(result)
+            }
+        }
+    })
+}
+
+fn queue_dir() -> PathBuf {
+    config_watcher::state_dir().join(QUEUE_DIR)
+}
+
+fn item_path(id: &str, ext: &str) -> PathBuf {
+    queue_dir().join(format!("{}.{}", id, ext))
+}
+
+fn remove_files(id: &str) {
+    let _ = std::fs::remove_file(item_path(id, "json"));
+    let _ = std::fs::remove_file(item_path(id, "body"));
+}
+
+fn load() -> Queue {
+    let mut items = Vec::new();
+    if let Ok(entries) = std::fs::read_dir(queue_dir()) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().map(|e| e != "json").unwrap_or(true) {
+                continue;
+            }
+            let item = std::fs::read(&path)
+                .ok()
+                .and_then(|data| serde_json::from_slice::<Item>(&data).ok());
+            match item {
+                Some(item) if path.with_extension("body").exists() => items.push(item),
+                _ => {
+                    eprintln!("[offline-queue] dropping unreadable {}", path.display());
+                    let _ = std::fs::remove_file(&path);
+                    let _ = std::fs::remove_file(path.with_extension("body"));
+                }
+            }
+        }
+    }
+    items.sort_by_key(|item| (item.queued_at, item.seq));
+    let next_seq = items.iter().map(|item| item.seq + 1).max().unwrap_or(0);
+    if !items.is_empty() {
+        eprintln!("[offline-queue] {} requests waiting from last session", items.len());
+    }
+    Queue {
+        items: items.into(),
+        next_seq,
+        in_flight: None,
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tauri::http::HeaderValue;
+
+    /// The queue is process-wide; tests take turns and start from empty.
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    fn fresh() -> std::sync::MutexGuard<'static, ()> {
+        let guard = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        config_watcher::test_home();
+        let _ = std::fs::remove_dir_all(queue_dir());
+        *QUEUE.lock().unwrap() = None;
+        guard
+    }
+
+    fn queue(method: Method, uri: &str, body: &[u8]) -> String {
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", HeaderValue::from_static("application/json"));
+        headers.insert(QUEUE_HEADER, HeaderValue::from_static("1"));
+        enqueue(&method, uri, &headers, body).unwrap()
+    }
+
+    fn queued_ids() -> Vec<String> {
+        list_offline_queue().into_iter().map(|q| q.id).collect()
+    }
+
+    fn done(item: &Item) -> Replay {
+        Replay::Done(QueueResult {
+            id: item.id.clone(),
+            method: item.method.clone(),
+            path: item.uri.clone(),
+            status: 201,
+            ok: true,
+            error: None,
+            body: None,
+        })
+    }
+
+    #[test]
+    fn opted_in_write_is_accepted_while_the_daemon_is_away() {
+        let _guard = fresh();
+        let request = Request::builder()
+            .method("POST")
+            .uri("hecate://localhost/api/notes?draft=1")
+            .header(QUEUE_HEADER, "1")
+            .body(b"{\"n\":1}".to_vec())
+            .unwrap();
+        let response = daemon_client::block_on(socket_proxy::proxy_request(request)).unwrap();
+        let (parts, body) = response.into_parts();
+        let mut buf = Vec::new();
+        daemon_client::block_on(body.for_each_chunk(|chunk| buf.extend_from_slice(chunk))).unwrap();
+
+        assert_eq!(parts.status, 202);
+        let id = parts.headers["x-hecate-queue-id"].to_str().unwrap().to_string();
+        assert_eq!(buf, accepted_body(&id));
+        assert_eq!(queued_ids(), vec![id]);
+        assert_eq!(list_offline_queue()[0].path, "/api/notes?draft=1");
+    }
+
+    #[test]
+    fn queue_survives_a_restart_in_order() {
+        let _guard = fresh();
+        let first = queue(Method::POST, "/api/a", b"{\"n\":1}");
+        let second = queue(Method::DELETE, "/api/b", b"");
+        *QUEUE.lock().unwrap() = None;
+
+        let listed = list_offline_queue();
+        assert_eq!(queued_ids(), vec![first, second.clone()]);
+        assert_eq!((listed[0].method.as_str(), listed[0].body_bytes), ("POST", 7));
+        assert_eq!((listed[1].method.as_str(), listed[1].body_bytes), ("DELETE", 0));
+        let item = with_queue(|queue| queue.items[0].clone());
+        assert_eq!(item.headers, vec![("content-type".to_string(), "application/json".to_string())]);
+
+        // New requests keep counting after the reloaded ones
+        let third = queue(Method::PUT, "/api/c", b"{}");
+        assert_eq!(queued_ids().last(), Some(&third));
+        assert_ne!(third, second);
+    }
+
+    #[test]
+    fn unreadable_entries_are_dropped_on_load() {
+        let _guard = fresh();
+        let kept = queue(Method::POST, "/api/a", b"{}");
+        std::fs::write(item_path("q-broken", "json"), b"not json").unwrap();
+        std::fs::write(item_path("q-broken", "body"), b"").unwrap();
+        *QUEUE.lock().unwrap() = None;
+
+        assert_eq!(queued_ids(), vec![kept]);
+        assert!(!item_path("q-broken", "json").exists());
+        assert!(!item_path("q-broken", "body").exists());
+    }
+
+    #[test]
+    fn busy_daemon_is_retried_then_replay_pauses() {
+        let _guard = fresh();
+        let id = queue(Method::POST, "/api/a", b"{}");
+        let mut busy_retries = 0;
+        for _ in 0..BUSY_RETRIES {
+            let item = next_item().unwrap();
+            let step = settle(&item, Replay::Busy(Duration::from_secs(1)), &mut busy_retries);
+            assert!(matches!(step, Step::Retry(delay) if delay == Duration::from_secs(1)));
+        }
+        let item = next_item().unwrap();
+        assert!(matches!(settle(&item, Replay::Busy(Duration::from_secs(1)), &mut busy_retries), Step::Pause));
+
+        // Still queued, and no longer in flight
+        let listed = list_offline_queue();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].id, id);
+        assert!(!listed[0].replaying);
+        assert!(item_path(&id, "body").exists());
+    }
+
+    #[test]
+    fn unreachable_daemon_pauses_and_done_moves_on() {
+        let _guard = fresh();
+        let first = queue(Method::POST, "/api/a", b"{}");
+        let second = queue(Method::POST, "/api/b", b"{}");
+        let mut busy_retries = 0;
+
+        let item = next_item().unwrap();
+        assert!(matches!(settle(&item, Replay::Unreachable, &mut busy_retries), Step::Pause));
+        assert_eq!(queued_ids(), vec![first.clone(), second.clone()]);
+
+        // A 429 before success does not count against the next request
+        let item = next_item().unwrap();
+        assert!(matches!(settle(&item, Replay::Busy(Duration::ZERO), &mut busy_retries), Step::Retry(_)));
+        let item = next_item().unwrap();
+        match settle(&item, done(&item), &mut busy_retries) {
+            Step::Report(result) => assert_eq!((result.id, result.status), (first.clone(), 201)),
+            _ => panic!("expected a result"),
+        }
+        assert_eq!(busy_retries, 0);
+        assert_eq!(queued_ids(), vec![second.clone()]);
+        assert!(!item_path(&first, "json").exists());
+        assert_eq!(next_item().unwrap().id, second);
+    }
+
+    #[test]
+    fn only_waiting_requests_can_be_cancelled() {
+        let _guard = fresh();
+        let first = queue(Method::POST, "/api/a", b"{}");
+        let second = queue(Method::POST, "/api/b", b"{}");
+
+        let in_flight = next_item().unwrap();
+        assert_eq!(in_flight.id, first);
+        assert!(list_offline_queue()[0].replaying);
+        assert!(cancel_queued_request(first.clone()).is_err());
+
+        cancel_queued_request(second.clone()).unwrap();
+        assert!(!item_path(&second, "body").exists());
+        assert!(cancel_queued_request(second).is_err());
+        assert_eq!(queued_ids(), vec![first]);
+        assert!(is_pending());
+    }
+}
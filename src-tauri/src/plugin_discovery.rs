@@ -1,34 +1,23 @@
 use serde::Serialize;
-use std::path::Path;
+
+use crate::config_watcher;
+use crate::plugin_name::PluginName;
 
 #[derive(Serialize, Clone)]
 pub struct PluginInfo {
-    pub name: String,
+    pub name: PluginName,
     pub socket_exists: bool,
 }
 
-/// Extract plugin name from a daemon directory name.
-/// Only supports: hecate-app-{name}d -> {name}
-/// Returns None for non-plugin dirs.
-fn extract_plugin_name(dir_name: &str) -> Option<String> {
-    dir_name
-        .strip_prefix("hecate-app-")
-        .and_then(|rest| rest.strip_suffix('d'))
-        .filter(|s| !s.is_empty())
-        .map(|s| s.to_string())
-}
-
 /// Scan ~/.hecate/ for plugin daemon directories.
 /// Matches hecate-app-*d directories.
 /// Returns a list of discovered plugins with their socket status.
 #[tauri::command]
 pub fn discover_plugins() -> Vec<PluginInfo> {
-    let home = match std::env::var("HOME") {
-        Ok(h) => h,
-        Err(_) => return Vec::new(),
+    let hecate_dir = match config_watcher::hecate_home() {
+        Some(dir) => dir,
+        None => return Vec::new(),
     };
-
-    let hecate_dir = Path::new(&home).join(".hecate");
     let entries = match std::fs::read_dir(&hecate_dir) {
         Ok(e) => e,
         Err(_) => return Vec::new(),
@@ -44,7 +33,7 @@ pub fn discover_plugins() -> Vec<PluginInfo> {
             continue;
         }
 
-        let plugin_name = match extract_plugin_name(&name_str) {
+        let plugin_name = match PluginName::from_dir_name(&name_str) {
             Some(n) => n,
             None => continue,
         };
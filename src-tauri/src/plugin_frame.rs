@@ -0,0 +1,95 @@
+//! The document each plugin UI runs in.
+//!
+//! The shell shows a plugin in an iframe at `hecate://{name}.plugin/_hecate/frame`
+//! instead of importing its component into the shell document. The frame
+//! loads `/ui/component.js` from the plugin's own host, mounts the element
+//! named in its manifest and hands it an `api` object with the same methods
+//! as the shell's `PluginApi`, whose root-relative paths reach that plugin's
+//! daemon. Everything the component does therefore carries the
+//! `{name}.plugin` origin, which is what `plugin_isolation` attributes
+//! requests by. The frame has no Tauri IPC: the capabilities only cover the
+//! shell's own origin. For WebSockets and SSE streams the frame `api` posts
+//! to the shell instead, whose bridge (`src/lib/plugin-bridge.ts`) runs the
+//! `ws_*` and `plugin_sse_stream` commands with the plugin fixed to the
+//! frame's own.
+//!
+//! The frame files are answered by the proxy itself; the plugin daemon never
+//! sees requests for them.
+
+use tauri::http::Method;
+
+use crate::plugin_isolation;
+
+/// Path prefix of the frame files on plugin hosts.
+pub const FRAME_PREFIX: &str = "/_hecate/";
+
+/// Content-Security-Policy sent with the frame files. Scripts only from the
+/// plugin's own origin; the daemon hosts stay reachable for `daemon_apis`
+/// grants, which `plugin_isolation` checks per request.
+pub const FRAME_CSP: &str = "default-src 'self'; script-src 'self'; style-src 'self' 'unsafe-inline'; \
+    connect-src 'self' hecate://localhost hecate://daemon; img-src 'self' data: blob:; media-src 'self' blob:";
+
+/// One built-in frame file.
+pub struct Asset {
+    pub content_type: &'static str,
+    pub body: &'static str,
+}
+
+const FRAME_HTML: Asset = Asset {
+    content_type: "text/html; charset=utf-8",
+    body: r#"<!doctype html>
+<html>
+<head>
+<meta charset="utf-8">
+<style>html, body { margin: 0; height: 100%; background: transparent; }</style>
+<script type="module" src="/_hecate/frame.js"></script>
+</head>
+<body></body>
+</html>
+"#,
+};
+
+// The frame scripts live with the frontend so its tests can check the
+// frame `api` against the shell's `PluginApi`.
+const FRAME_JS: Asset = Asset {
+    content_type: "text/javascript; charset=utf-8",
+    body: include_str!("../../src/lib/plugin-frame/frame.js"),
+};
+
+const API_JS: Asset = Asset {
+    content_type: "text/javascript; charset=utf-8",
+    body: include_str!("../../src/lib/plugin-frame/api.js"),
+};
+
+/// The frame file for a request, if `host` is a plugin host and `path` one
+/// of the reserved frame paths.
+pub fn asset(host: Option<&str>, method: &Method, path: &str) -> Option<&'static Asset> {
+    let on_plugin_host = host.and_then(plugin_isolation::plugin_from_host).is_some();
+    if *method != Method::GET || !on_plugin_host {
+        return None;
+    }
+    match path.strip_prefix(FRAME_PREFIX)? {
+        "frame" => Some(&FRAME_HTML),
+        "frame.js" => Some(&FRAME_JS),
+        "api.js" => Some(&API_JS),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_is_served_on_plugin_hosts_only() {
+        let html = asset(Some("demo.plugin"), &Method::GET, "/_hecate/frame").unwrap();
+        assert!(html.body.contains("/_hecate/frame.js"));
+        let js = asset(Some("demo.plugin"), &Method::GET, "/_hecate/frame.js").unwrap();
+        assert!(js.body.contains("./api.js"));
+        assert!(asset(Some("demo.plugin"), &Method::GET, "/_hecate/api.js").is_some());
+        assert!(asset(Some("demo.plugin"), &Method::GET, "/_hecate/other").is_none());
+        assert!(asset(Some("demo.plugin"), &Method::POST, "/_hecate/frame").is_none());
+        assert!(asset(Some("localhost"), &Method::GET, "/_hecate/frame").is_none());
+        assert!(asset(None, &Method::GET, "/_hecate/frame").is_none());
+    }
+}
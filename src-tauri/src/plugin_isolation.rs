@@ -0,0 +1,284 @@
+//! Per-plugin origin isolation for the `hecate://` proxy.
+//!
+//! A request is attributed to a plugin when the webview reports a plugin
+//! origin for it: the `Origin` (or, failing that, `Referer`) host is
+//! `{name}.plugin` or `{name}.plugin.localhost`. Plugin UIs run in a frame
+//! on that host (see `plugin_frame`), never in the shell document, and page
+//! scripts cannot set either header, so a plugin cannot pose as the shell or
+//! as another plugin. A plugin-shaped origin whose name is not
+//! a valid [`PluginName`] is attributed to no one and may reach nothing.
+//!
+//! Attribution fails closed. A frame can send a request with neither header
+//! (`referrerPolicy: 'no-referrer'` on a GET), so such a request is
+//! attributed by the host it was sent to: a `{name}.plugin` host stands for
+//! that plugin, and only the shell's own hosts (`localhost`, the daemon
+//! hosts) stand for the shell. Any other origin, or a missing one elsewhere,
+//! may reach nothing.
+//!
+//! An attributed plugin may reach its own `/plugin/{name}/*` namespace and
+//! only those main-daemon APIs its manifest grants under `daemon_apis`
+//! (same rule shape as the access policy). Manifests are fetched from the
+//! plugin socket on first use and forgotten when the plugin watcher sees the
+//! socket come or go.
+
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::http::header::{ORIGIN, REFERER};
+use tauri::http::{HeaderMap, Method};
+
+use crate::daemon_client;
+use crate::plugin_name::PluginName;
+use crate::proxy_policy::Rule;
+use crate::socket_proxy::{resolve_plugin_socket_path, Target};
+
+const MANIFEST_PATH: &str = "/manifest";
+/// Hosts the shell itself is served from or sends its requests to.
+const SHELL_HOSTS: &[&str] = &["localhost", "tauri.localhost", "daemon", "daemon.localhost"];
+const MANIFEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Who issued a proxied request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Caller {
+    /// The hecate-web shell itself.
+    Shell,
+    /// A plugin UI running from its own origin.
+    Plugin(PluginName),
+    /// A `*.plugin` origin with an invalid plugin name.
+    InvalidPlugin(String),
+    /// An origin that is neither the shell nor a plugin, or no origin on a
+    /// request to a host other than the shell's.
+    Unknown(String),
+}
+
+#[derive(Deserialize)]
+struct PluginManifest {
+    #[serde(default)]
+    daemon_apis: Vec<Rule>,
+}
+
+/// Daemon API grants per plugin, keyed by plugin name.
+static GRANTS: Mutex<Option<HashMap<PluginName, Vec<Rule>>>> = Mutex::new(None);
+
+/// Attribute a request to its caller from the webview-set origin headers,
+/// or from the `host` it was sent to when it carries neither.
+pub fn caller_of(headers: &HeaderMap, host: Option<&str>) -> Caller {
+    let origin = headers
+        .get(ORIGIN)
+        .or_else(|| headers.get(REFERER))
+        .map(|v| v.to_str().unwrap_or_default());
+    let caller_host = match (origin, host) {
+        (Some(origin), _) => match origin_host(origin) {
+            Some(h) => h,
+            None => return Caller::Unknown(origin.to_string()),
+        },
+        (None, Some(host)) => host,
+        (None, None) => return Caller::Unknown(String::new()),
+    };
+    if let Some(name) = plugin_from_host(caller_host) {
+        return match PluginName::parse(&name) {
+            Ok(name) => Caller::Plugin(name),
+            Err(_) => Caller::InvalidPlugin(name),
+        };
+    }
+    if SHELL_HOSTS.contains(&caller_host.to_ascii_lowercase().as_str()) {
+        Caller::Shell
+    } else {
+        Caller::Unknown(caller_host.to_string())
+    }
+}
+
+/// Host part of an origin or referer URL (`scheme://host[:port]/...`).
+fn origin_host(url: &str) -> Option<&str> {
+    let rest = url.split_once("://")?.1;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    Some(authority.rsplit_once(':').map(|(h, _)| h).unwrap_or(authority)).filter(|h| !h.is_empty())
+}
+
+/// `trader.plugin` or `trader.plugin.localhost` -> `trader`
+pub fn plugin_from_host(host: &str) -> Option<String> {
+    let host = host.to_ascii_lowercase();
+    let host = host.strip_suffix(".localhost").unwrap_or(&host);
+    host.strip_suffix(".plugin")
+        .filter(|name| !name.is_empty() && !name.contains('.'))
+        .map(|name| name.to_string())
+}
+
+/// Whether `caller` may send `method path` to `target`.
+pub async fn permits(caller: &Caller, target: &Target, method: &Method, path: &str) -> bool {
+    let plugin = match caller {
+        Caller::Shell => return true,
+        Caller::Plugin(name) => name,
+        Caller::InvalidPlugin(name) => {
+            eprintln!("[plugin-isolation] denied invalid plugin origin {:?} -> {} {}", name, method, path);
+            return false;
+        }
+        Caller::Unknown(origin) => {
+            eprintln!("[plugin-isolation] denied unknown origin {:?} -> {} {}", origin, method, path);
+            return false;
+        }
+    };
+    let allowed = match target {
+        Target::Plugin(name) => name == plugin,
+        Target::Daemon => grants_for(plugin)
+            .await
+            .iter()
+            .any(|rule| rule.allows(method.as_str(), path)),
+    };
+    if !allowed {
+        eprintln!(
+            "[plugin-isolation] denied plugin {} -> {} {} on {}",
+            plugin, method, path, target
+        );
+    }
+    allowed
+}
+
+/// Drop the cached manifest grants for a plugin (socket came or went).
+pub fn forget(plugin_name: &PluginName) {
+    if let Ok(mut grants) = GRANTS.lock() {
+        if let Some(map) = grants.as_mut() {
+            map.remove(plugin_name);
+        }
+    }
+}
+
+async fn grants_for(plugin_name: &PluginName) -> Vec<Rule> {
+    if let Some(rules) = GRANTS
+        .lock()
+        .ok()
+        .and_then(|g| g.as_ref().and_then(|m| m.get(plugin_name).cloned()))
+    {
+        return rules;
+    }
+
+    match fetch_manifest(plugin_name).await {
+        Ok(manifest) => {
+            if let Ok(mut grants) = GRANTS.lock() {
+                grants
+                    .get_or_insert_with(HashMap::new)
+                    .insert(plugin_name.clone(), manifest.daemon_apis.clone());
+            }
+            manifest.daemon_apis
+        }
+        Err(e) => {
+            // Not cached: the next request tries again once the plugin is up
+            eprintln!("[plugin-isolation] manifest fetch failed for {}: {}", plugin_name, e);
+            Vec::new()
+        }
+    }
+}
+
+async fn fetch_manifest(plugin_name: &PluginName) -> Result<PluginManifest, Box<dyn std::error::Error>> {
+    let socket_path = resolve_plugin_socket_path(plugin_name);
+    if !Path::new(&socket_path).exists() {
+        return Err("plugin socket not found".into());
+    }
+    let request = daemon_client::request(Method::GET, &socket_path, MANIFEST_PATH)
+        .body(daemon_client::empty())?;
+
+    let response = tokio::time::timeout(MANIFEST_TIMEOUT, daemon_client::send(request))
+        .await
+        .map_err(|_| "manifest request timed out")??;
+    if !response.status().is_success() {
+        return Err(format!("manifest returned {}", response.status()).into());
+    }
+
+    let buf = daemon_client::read_to_end(&mut response.into_body(), MANIFEST_TIMEOUT)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(serde_json::from_slice(&buf)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tauri::http::HeaderValue;
+
+    fn headers(name: tauri::http::HeaderName, value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_static(value));
+        headers
+    }
+
+    fn caller_of_origin(name: tauri::http::HeaderName, value: &'static str) -> Caller {
+        caller_of(&headers(name, value), Some("localhost"))
+    }
+
+    fn plugin(name: &str) -> PluginName {
+        PluginName::parse(name).unwrap()
+    }
+
+    #[test]
+    fn caller_from_origin_or_referer() {
+        assert_eq!(caller_of_origin(ORIGIN, "hecate://demo.plugin"), Caller::Plugin(plugin("demo")));
+        assert_eq!(
+            caller_of_origin(ORIGIN, "http://demo.plugin.localhost:1420"),
+            Caller::Plugin(plugin("demo"))
+        );
+        // Same-origin GETs from the frame carry only a Referer
+        assert_eq!(
+            caller_of_origin(REFERER, "hecate://demo.plugin/_hecate/frame"),
+            Caller::Plugin(plugin("demo"))
+        );
+        assert_eq!(caller_of_origin(ORIGIN, "tauri://localhost"), Caller::Shell);
+        assert_eq!(caller_of_origin(ORIGIN, "hecate://localhost"), Caller::Shell);
+        assert_eq!(
+            caller_of_origin(ORIGIN, "hecate://-demo.plugin"),
+            Caller::InvalidPlugin("-demo".to_string())
+        );
+    }
+
+    #[test]
+    fn caller_without_origin_is_the_host_it_asked() {
+        let none = HeaderMap::new();
+        // A frame dropping both headers is still its own plugin
+        assert_eq!(caller_of(&none, Some("demo.plugin")), Caller::Plugin(plugin("demo")));
+        assert_eq!(caller_of(&none, Some("demo.plugin.localhost")), Caller::Plugin(plugin("demo")));
+        assert_eq!(caller_of(&none, Some("localhost")), Caller::Shell);
+        assert_eq!(caller_of(&none, Some("daemon")), Caller::Shell);
+        assert_eq!(caller_of(&none, Some("elsewhere")), Caller::Unknown("elsewhere".to_string()));
+        assert_eq!(caller_of(&none, None), Caller::Unknown(String::new()));
+        // An origin, when present, wins over the host
+        assert_eq!(
+            caller_of(&headers(ORIGIN, "hecate://demo.plugin"), Some("localhost")),
+            Caller::Plugin(plugin("demo"))
+        );
+    }
+
+    #[test]
+    fn unknown_origins_are_not_the_shell() {
+        assert_eq!(
+            caller_of_origin(ORIGIN, "https://example.com"),
+            Caller::Unknown("example.com".to_string())
+        );
+        assert_eq!(caller_of_origin(ORIGIN, "null"), Caller::Unknown("null".to_string()));
+        assert_eq!(caller_of_origin(REFERER, "hecate:///x"), Caller::Unknown("hecate:///x".to_string()));
+    }
+
+    #[tokio::test]
+    async fn headerless_plugin_request_stays_isolated() {
+        let caller = caller_of(&HeaderMap::new(), Some("demo.plugin"));
+        assert!(!permits(&caller, &Target::Plugin(plugin("trader")), &Method::GET, "/api/hello").await);
+        let unknown = caller_of(&HeaderMap::new(), None);
+        assert!(!permits(&unknown, &Target::Plugin(plugin("trader")), &Method::GET, "/").await);
+    }
+
+    #[tokio::test]
+    async fn plugin_frame_is_refused_another_plugins_socket() {
+        let caller = caller_of_origin(REFERER, "hecate://demo.plugin/_hecate/frame");
+        assert!(permits(&caller, &Target::Plugin(plugin("demo")), &Method::GET, "/api/hello").await);
+        assert!(!permits(&caller, &Target::Plugin(plugin("trader")), &Method::GET, "/api/hello").await);
+        assert!(!permits(&caller, &Target::Plugin(plugin("trader")), &Method::POST, "/api/orders").await);
+    }
+
+    #[tokio::test]
+    async fn shell_may_reach_any_plugin_and_invalid_origins_nothing() {
+        let shell = caller_of_origin(ORIGIN, "tauri://localhost");
+        assert!(permits(&shell, &Target::Plugin(plugin("trader")), &Method::GET, "/").await);
+        let invalid = Caller::InvalidPlugin("a.b".to_string());
+        assert!(!permits(&invalid, &Target::Plugin(plugin("trader")), &Method::GET, "/").await);
+    }
+}
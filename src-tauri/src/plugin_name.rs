@@ -0,0 +1,154 @@
+//! Validated plugin identifiers.
+//!
+//! A plugin name ends up in filesystem paths (`~/.hecate/hecate-app-{name}d`),
+//! container image names and systemd unit names, and arrives from URL paths,
+//! origins and command arguments. [`PluginName`] can only hold a name that is
+//! safe in all of those: 1–64 characters of lowercase ASCII letters, digits,
+//! `-` and `_`, starting with a letter or digit. Nothing else is accepted, so
+//! `..`, `/` and friends never reach a path.
+//!
+//! Command arguments typed as `PluginName` are checked when Tauri
+//! deserializes them.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+const MAX_LEN: usize = 64;
+const DIR_PREFIX: &str = "hecate-app-";
+const DIR_SUFFIX: &str = "d";
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct PluginName(String);
+
+/// Why a string is not a plugin name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidPluginName(String);
+
+impl PluginName {
+    pub fn parse(name: &str) -> Result<Self, InvalidPluginName> {
+        let valid = !name.is_empty()
+            && name.len() <= MAX_LEN
+            && name.bytes().next().is_some_and(|b| b.is_ascii_lowercase() || b.is_ascii_digit())
+            && name
+                .bytes()
+                .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-' || b == b'_');
+        if valid {
+            Ok(PluginName(name.to_string()))
+        } else {
+            Err(InvalidPluginName(name.to_string()))
+        }
+    }
+
+    /// The plugin a daemon directory belongs to: `hecate-app-{name}d`.
+    /// `None` for other directories and for invalid names.
+    pub fn from_dir_name(dir_name: &str) -> Option<Self> {
+        let name = dir_name.strip_prefix(DIR_PREFIX)?.strip_suffix(DIR_SUFFIX)?;
+        match Self::parse(name) {
+            Ok(name) => Some(name),
+            Err(e) => {
+                if !name.is_empty() {
+                    eprintln!("[plugin-name] ignoring {}: {}", dir_name, e);
+                }
+                None
+            }
+        }
+    }
+
+    /// `hecate-app-{name}d`: the daemon's directory, image and unit name.
+    pub fn dir_name(&self) -> String {
+        format!("{}{}{}", DIR_PREFIX, self.0, DIR_SUFFIX)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for PluginName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl TryFrom<String> for PluginName {
+    type Error = InvalidPluginName;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        Self::parse(&name)
+    }
+}
+
+impl From<PluginName> for String {
+    fn from(name: PluginName) -> Self {
+        name.0
+    }
+}
+
+impl fmt::Display for InvalidPluginName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid plugin name {:?}: expected 1-{} lowercase letters, digits, '-' or '_', starting with a letter or digit",
+            self.0, MAX_LEN
+        )
+    }
+}
+
+impl std::error::Error for InvalidPluginName {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid_names() {
+        for name in ["trader", "a", "7", "my-plugin", "my_plugin", "v2", &"a".repeat(MAX_LEN)] {
+            assert_eq!(PluginName::parse(name).map(|n| n.to_string()), Ok(name.to_string()));
+        }
+    }
+
+    #[test]
+    fn invalid_names() {
+        let too_long = "a".repeat(MAX_LEN + 1);
+        let cases = [
+            "",
+            &too_long,
+            // Path separators and traversal
+            "a/b",
+            "/trader",
+            "trader/",
+            "a\\b",
+            "..",
+            ".",
+            "../trader",
+            "trader..",
+            // Uppercase
+            "Trader",
+            "TRADER",
+            // Must start with a letter or digit
+            "-trader",
+            "_trader",
+            // Non-ASCII and other characters
+            "trädér",
+            "tradеr", // Cyrillic е
+            "trader\0",
+            "tra der",
+            "trader.plugin",
+            "trader%2f",
+        ];
+        for name in cases {
+            assert!(PluginName::parse(name).is_err(), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn dir_names() {
+        let name = PluginName::parse("trader").unwrap();
+        assert_eq!(name.dir_name(), "hecate-app-traderd");
+        assert_eq!(PluginName::from_dir_name("hecate-app-traderd"), Some(name));
+        assert_eq!(PluginName::from_dir_name("hecate-app-d"), None);
+        assert_eq!(PluginName::from_dir_name("hecate-app-..d"), None);
+        assert_eq!(PluginName::from_dir_name("hecate-daemon"), None);
+    }
+}
@@ -1,23 +1,34 @@
-use std::io::{BufRead, BufReader, Read, Write};
-use std::os::unix::net::UnixStream;
+use tauri::http::header::ACCEPT;
+use tauri::http::Method;
 use tauri::{AppHandle, Emitter};
 
+use crate::correlation;
+use crate::daemon_client;
+use crate::event_map;
+use crate::har_recorder;
+use crate::plugin_name::PluginName;
 use crate::socket_proxy::resolve_plugin_socket_path;
+use crate::sse;
 use crate::traffic;
 
 /// Generic SSE stream proxy for plugin daemons.
 /// Connects to a plugin's Unix socket, makes a GET request to the given path,
-/// and forwards SSE events as Tauri events.
+/// and forwards SSE events as Tauri events on `event_name`. With
+/// `typed_events`, events carrying an `event:` type go out as
+/// `{event_name}:{type}` instead.
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn plugin_sse_stream(
     app: AppHandle,
     stream_id: String,
-    plugin: String,
+    plugin: PluginName,
     path: String,
     event_name: String,
     done_event: String,
     error_event: String,
+    typed_events: Option<bool>,
 ) -> Result<(), String> {
+    let typed_events = typed_events.unwrap_or(false);
     eprintln!(
         "[plugin_sse_stream] starting stream_id={} plugin={} path={}",
         stream_id, plugin, path
@@ -25,7 +36,7 @@ pub async fn plugin_sse_stream(
 
     std::thread::spawn(move || {
         let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-            do_plugin_sse_stream(&app, &plugin, &path, &event_name)
+            daemon_client::block_on(do_plugin_sse_stream(&app, &plugin, &path, &event_name, typed_events))
         }));
 
         match result {
@@ -60,126 +71,72 @@ pub async fn plugin_sse_stream(
     Ok(())
 }
 
-fn do_plugin_sse_stream(
+async fn do_plugin_sse_stream(
     app: &AppHandle,
-    plugin: &str,
+    plugin: &PluginName,
     path: &str,
     event_name: &str,
+    typed_events: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let socket_path = resolve_plugin_socket_path(plugin);
-    let mut stream = UnixStream::connect(&socket_path)?;
-    stream.set_read_timeout(None)?;
 
-    let http_req = format!(
-        "GET {} HTTP/1.1\r\nHost: localhost\r\nAccept: text/event-stream\r\nConnection: keep-alive\r\n\r\n",
-        path
+    let mut request = daemon_client::request(Method::GET, &socket_path, path)
+        .header(ACCEPT, "text/event-stream")
+        .body(daemon_client::empty())?;
+    let ids = correlation::Ids::generate();
+    ids.apply(request.headers_mut());
+    traffic::record_tx(daemon_client::head_len(&request) as u64);
+    let capture = har_recorder::begin(
+        request.method(),
+        &format!("hecate://localhost/plugin/{}{}", plugin, path),
+        request.headers(),
+        &[],
     );
 
-    stream.write_all(http_req.as_bytes())?;
-
-    let mut reader = BufReader::new(stream);
-
-    // Read status line
-    let mut status_line = String::new();
-    reader.read_line(&mut status_line)?;
-    let status_code = parse_status(&status_line);
-    eprintln!("[plugin_sse_stream] status: {}", status_code);
-
-    // Read headers
-    let mut is_chunked = false;
-    loop {
-        let mut line = String::new();
-        let n = reader.read_line(&mut line)?;
-        if n == 0 {
-            break;
-        }
-        let trimmed = line.trim();
-        if trimmed.is_empty() {
-            break;
-        }
-        let lower = line.to_lowercase();
-        if lower.contains("transfer-encoding") && lower.contains("chunked") {
-            is_chunked = true;
-        }
+    let response = daemon_client::send(request).await?;
+    let status_code = response.status().as_u16();
+    eprintln!("[plugin_sse_stream] status: {} (request {})", status_code, ids.request_id);
+    if let Some(c) = &capture {
+        c.response(response.status(), response.headers());
     }
 
     if status_code >= 400 {
         return Err(format!("plugin {} returned {}", plugin, status_code).into());
     }
 
-    if !is_chunked {
-        eprintln!("[plugin_sse_stream] WARNING: chunked not detected, forcing chunked mode");
-    }
-
-    read_sse_chunked(app, event_name, &mut reader)
-}
-
-fn read_sse_chunked(
-    app: &AppHandle,
-    event_name: &str,
-    reader: &mut BufReader<UnixStream>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let mut leftover = String::new();
-
-    loop {
-        let mut size_line = String::new();
-        let bytes_read = reader.read_line(&mut size_line)?;
-        if bytes_read == 0 {
-            break;
-        }
-
-        let trimmed = size_line.trim();
-        let size = match usize::from_str_radix(trimmed, 16) {
-            Ok(s) => s,
-            Err(_) => continue,
-        };
+    let mut body = response.into_body();
+    let mut decoder = sse::Decoder::new();
 
-        if size == 0 {
-            break;
+    while let Some(chunk) = daemon_client::next_chunk(&mut body).await {
+        let chunk = chunk?;
+        traffic::record_rx(chunk.len() as u64);
+        if let Some(c) = &capture {
+            c.body(&chunk);
         }
-
-        let mut chunk_buf = vec![0u8; size];
-        reader.read_exact(&mut chunk_buf)?;
-        traffic::record_rx(size as u64);
-
-        // Read trailing CRLF after chunk data
-        let mut _trail = String::new();
-        let _ = reader.read_line(&mut _trail);
-
-        let chunk_str = String::from_utf8_lossy(&chunk_buf);
-        leftover.push_str(&chunk_str);
-
-        while let Some(newline_pos) = leftover.find('\n') {
-            let line = leftover[..newline_pos].trim().to_string();
-            leftover = leftover[newline_pos + 1..].to_string();
-
-            if line.is_empty() || line.starts_with(':') {
-                continue;
-            }
-
-            process_sse_line(app, event_name, &line);
+        for event in decoder.feed(&chunk) {
+            emit_event(app, event_name, typed_events, &event);
         }
     }
 
     Ok(())
 }
 
-fn process_sse_line(app: &AppHandle, event_name: &str, line: &str) {
-    let json_str = line.strip_prefix("data: ").unwrap_or(line);
-
-    if json_str == "[DONE]" {
+/// Events go out as `event_name`; typed ones as `{event_name}:{type}` when
+/// the caller asked for `typed_events` and that makes a valid event name.
+/// JSON data is passed on parsed, anything else as a string; a `[DONE]`
+/// sentinel is dropped.
+fn emit_event(app: &AppHandle, event_name: &str, typed_events: bool, event: &sse::Event) {
+    if event.data == "[DONE]" {
         return;
     }
-
-    if let Ok(value) = serde_json::from_str::<serde_json::Value>(json_str) {
-        let _ = app.emit(event_name, value);
-    }
-}
-
-fn parse_status(status_line: &str) -> u16 {
-    status_line
-        .split_whitespace()
-        .nth(1)
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(500)
+    let typed_name = format!("{}:{}", event_name, event.event_type);
+    let typed = typed_events && event.event_type != "message" && event_map::valid_event_name(&typed_name);
+    let name = if typed {
+        typed_name.as_str()
+    } else {
+        event_name
+    };
+    let payload = serde_json::from_str::<serde_json::Value>(&event.data)
+        .unwrap_or_else(|_| serde_json::Value::String(event.data.clone()));
+    let _ = app.emit(name, payload);
 }
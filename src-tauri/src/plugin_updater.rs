@@ -2,9 +2,12 @@ use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use tauri::{AppHandle, Emitter};
 
+use crate::config_watcher;
+use crate::plugin_name::PluginName;
+
 #[derive(Serialize, Clone)]
 pub struct PluginUpdate {
-    pub name: String,
+    pub name: PluginName,
     pub installed_version: String,
     pub latest_version: String,
     pub body: String,
@@ -29,8 +32,7 @@ fn is_newer(remote: &str, local: &str) -> bool {
 }
 
 fn gitops_apps_dir() -> Option<PathBuf> {
-    let home = std::env::var("HOME").ok()?;
-    Some(PathBuf::from(home).join(".hecate").join("gitops").join("apps"))
+    Some(config_watcher::hecate_home()?.join("gitops").join("apps"))
 }
 
 fn parse_installed_version(container_path: &PathBuf) -> Option<String> {
@@ -48,9 +50,8 @@ fn parse_installed_version(container_path: &PathBuf) -> Option<String> {
 }
 
 /// Discover plugin names from ~/.hecate/hecate-app-*d directories.
-fn discover_plugin_names() -> Result<Vec<String>, String> {
-    let home = std::env::var("HOME").map_err(|e| e.to_string())?;
-    let hecate_dir = PathBuf::from(&home).join(".hecate");
+fn discover_plugin_names() -> Result<Vec<PluginName>, String> {
+    let hecate_dir = config_watcher::hecate_home().ok_or("HOME is not set")?;
     let entries = std::fs::read_dir(&hecate_dir).map_err(|e| e.to_string())?;
 
     let mut plugins = Vec::new();
@@ -63,12 +64,8 @@ fn discover_plugin_names() -> Result<Vec<String>, String> {
             continue;
         }
 
-        if let Some(plugin_name) = name_str
-            .strip_prefix("hecate-app-")
-            .and_then(|rest| rest.strip_suffix('d'))
-            .filter(|s| !s.is_empty())
-        {
-            plugins.push(plugin_name.to_string());
+        if let Some(plugin_name) = PluginName::from_dir_name(&name_str) {
+            plugins.push(plugin_name);
         }
     }
 
@@ -88,7 +85,7 @@ pub async fn check_plugin_updates() -> Result<Vec<PluginUpdate>, String> {
     let mut updates = Vec::new();
 
     for name in &plugin_names {
-        let container_file = apps_dir.join(format!("hecate-app-{}d.container", name));
+        let container_file = apps_dir.join(format!("{}.container", name.dir_name()));
 
         let installed = match parse_installed_version(&container_file) {
             Some(v) => v,
@@ -168,18 +165,18 @@ pub async fn check_plugin_updates() -> Result<Vec<PluginUpdate>, String> {
 #[tauri::command]
 pub async fn install_plugin_update(
     app: AppHandle,
-    name: String,
+    name: PluginName,
     version: String,
 ) -> Result<(), String> {
     let apps_dir = gitops_apps_dir().ok_or("Cannot determine gitops apps directory")?;
 
-    let container_file = apps_dir.join(format!("hecate-app-{}d.container", name));
+    let container_file = apps_dir.join(format!("{}.container", name.dir_name()));
     if !container_file.exists() {
         return Err(format!("No .container file found for plugin {}", name));
     }
 
-    let image_prefix = format!("ghcr.io/hecate-social/hecate-app-{}d:", name);
-    let service_name = format!("hecate-app-{}d", name);
+    let image_prefix = format!("ghcr.io/hecate-social/{}:", name.dir_name());
+    let service_name = name.dir_name();
 
     // Read current .container file
     let content =
@@ -193,7 +190,7 @@ pub async fn install_plugin_update(
         .lines()
         .map(|line| {
             let trimmed = line.trim();
-            if trimmed.starts_with("Image=") && trimmed.contains(&format!("hecate-app-{}d:", name)) {
+            if trimmed.starts_with("Image=") && trimmed.contains(&format!("{}:", name.dir_name())) {
                 found = true;
                 new_image.clone()
             } else {
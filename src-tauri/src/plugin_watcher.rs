@@ -6,6 +6,10 @@ use std::sync::mpsc;
 use std::time::Duration;
 use tauri::Emitter;
 
+use crate::config_watcher;
+use crate::plugin_isolation;
+use crate::plugin_name::PluginName;
+
 const SOCKET_NAME: &str = "api.sock";
 const RECHECK_INTERVAL: Duration = Duration::from_secs(30);
 
@@ -16,11 +20,7 @@ pub struct PluginEvent {
 }
 
 fn hecate_base() -> PathBuf {
-    if let Ok(home) = std::env::var("HOME") {
-        PathBuf::from(home).join(".hecate")
-    } else {
-        PathBuf::from("/run/hecate")
-    }
+    config_watcher::hecate_home().unwrap_or_else(|| PathBuf::from("/run/hecate"))
 }
 
 /// Check if a directory name matches a plugin daemon pattern: hecate-app-*d
@@ -29,20 +29,18 @@ fn is_plugin_dir(name: &str) -> bool {
 }
 
 /// Extract plugin name from a daemon directory name.
-/// Only supports: hecate-app-{name}d -> {name}
-fn extract_plugin_name(dir_name: &str) -> Option<String> {
-    dir_name
-        .strip_prefix("hecate-app-")
-        .and_then(|rest| rest.strip_suffix('d'))
-        .filter(|s| !s.is_empty())
-        .map(|s| s.to_string())
+/// Only supports: hecate-app-{name}d -> {name}, with a valid name
+fn extract_plugin_name(dir_name: &str) -> Option<PluginName> {
+    PluginName::from_dir_name(dir_name)
 }
 
 fn sockets_dir(base: &PathBuf, dir_name: &str) -> PathBuf {
     base.join(dir_name).join("sockets")
 }
 
-fn emit_plugin(app: &tauri::AppHandle, name: &str, event_type: &str) {
+fn emit_plugin(app: &tauri::AppHandle, name: &PluginName, event_type: &str) {
+    // Manifest grants may change whenever the plugin daemon comes or goes
+    plugin_isolation::forget(name);
     let payload = PluginEvent {
         name: name.to_string(),
         event_type: event_type.to_string(),
@@ -53,7 +51,7 @@ fn emit_plugin(app: &tauri::AppHandle, name: &str, event_type: &str) {
     }
 }
 
-fn scan_existing_plugins(base: &PathBuf) -> Vec<(String, String)> {
+fn scan_existing_plugins(base: &PathBuf) -> Vec<(PluginName, String)> {
     let entries = match std::fs::read_dir(base) {
         Ok(e) => e,
         Err(_) => return Vec::new(),
@@ -0,0 +1,209 @@
+//! Typed failures of the `hecate://` proxy and their HTTP mapping.
+//!
+//! Every variant has a stable `code` the frontend can switch on, a status
+//! (400 malformed request or plugin name, 403 policy or plugin-isolation
+//! denial, 404 plugin not installed, 413 body over the limit, 429 too many
+//! requests in flight, 502 bad upstream, 503 upstream down, 504 upstream
+//! too slow) and, where a retry is likely to help, a `Retry-After` hint.
+
+use std::fmt;
+use tauri::http::Response;
+
+use crate::daemon_client;
+use crate::plugin_name::InvalidPluginName;
+
+#[derive(Debug)]
+pub enum ProxyError {
+    /// The request could not be turned into a daemon request (bad path, header).
+    InvalidRequest(String),
+    /// The access policy does not allow this method and path.
+    Forbidden(String),
+    /// The request body is over the configured limit (in bytes).
+    PayloadTooLarge(u64),
+    /// The target already has as many requests in flight and queued as its
+    /// concurrency limit allows.
+    TooManyRequests(String),
+    /// A plugin tried to reach outside its namespace and manifest grants.
+    NotGranted(String),
+    /// The plugin named by `/plugin/{name}` or a `{name}.plugin` host is not
+    /// a valid plugin name.
+    InvalidPluginName(InvalidPluginName),
+    /// `/plugin/{name}` or a `{name}.plugin` host names a plugin that is not
+    /// installed.
+    UnknownPlugin(String),
+    /// The target socket file does not exist — daemon not running.
+    SocketMissing(String),
+    /// The socket exists but nothing is accepting on it.
+    ConnectionRefused(String),
+    /// The daemon did not answer within the proxy timeout.
+    Timeout,
+    /// The daemon answered with something that is not valid HTTP.
+    MalformedResponse(String),
+    /// Any other transport failure talking to the daemon.
+    Upstream(String),
+    /// Replaying a HAR file and it holds no response for this request.
+    ReplayMiss(String),
+}
+
+impl ProxyError {
+    pub fn status(&self) -> u16 {
+        match self {
+            ProxyError::InvalidRequest(_) => 400,
+            ProxyError::Forbidden(_) => 403,
+            ProxyError::NotGranted(_) => 403,
+            ProxyError::PayloadTooLarge(_) => 413,
+            ProxyError::TooManyRequests(_) => 429,
+            ProxyError::InvalidPluginName(_) => 400,
+            ProxyError::UnknownPlugin(_) => 404,
+            ProxyError::SocketMissing(_) => 503,
+            ProxyError::ConnectionRefused(_) => 503,
+            ProxyError::Timeout => 504,
+            ProxyError::MalformedResponse(_) => 502,
+            ProxyError::Upstream(_) => 502,
+            ProxyError::ReplayMiss(_) => 502,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ProxyError::InvalidRequest(_) => "invalid_request",
+            ProxyError::Forbidden(_) => "policy_denied",
+            ProxyError::NotGranted(_) => "plugin_not_granted",
+            ProxyError::PayloadTooLarge(_) => "payload_too_large",
+            ProxyError::TooManyRequests(_) => "too_many_requests",
+            ProxyError::InvalidPluginName(_) => "invalid_plugin_name",
+            ProxyError::UnknownPlugin(_) => "unknown_plugin",
+            ProxyError::SocketMissing(_) => "socket_missing",
+            ProxyError::ConnectionRefused(_) => "connection_refused",
+            ProxyError::Timeout => "upstream_timeout",
+            ProxyError::MalformedResponse(_) => "malformed_upstream_response",
+            ProxyError::Upstream(_) => "upstream_error",
+            ProxyError::ReplayMiss(_) => "replay_miss",
+        }
+    }
+
+    /// Seconds the caller should wait before retrying, if a retry makes sense.
+    pub fn retry_after(&self) -> Option<u32> {
+        match self {
+            ProxyError::SocketMissing(_) => Some(5),
+            ProxyError::ConnectionRefused(_) => Some(2),
+            ProxyError::TooManyRequests(_) => Some(1),
+            _ => None,
+        }
+    }
+
+    /// Build the JSON error response sent back to the webview.
+    ///
+    /// `error` carries the code as well, since that is what `api.ts` already
+    /// reads; `message` is the human-readable detail.
+    pub fn into_response(self) -> Response<Vec<u8>> {
+        let body = serde_json::json!({
+            "ok": false,
+            "error": self.code(),
+            "code": self.code(),
+            "message": self.to_string(),
+        });
+
+        let mut builder = Response::builder()
+            .status(self.status())
+            .header("Content-Type", "application/json")
+            .header("Access-Control-Allow-Origin", "*")
+            .header("Access-Control-Expose-Headers", "*");
+        if let Some(secs) = self.retry_after() {
+            builder = builder.header("Retry-After", secs);
+        }
+        builder
+            .body(body.to_string().into_bytes())
+            .expect("static error response is valid")
+    }
+
+    /// Classify a failure from the pooled daemon client.
+    pub fn from_client(err: daemon_client::Error, socket_path: &str) -> Self {
+        if err.is_connect() {
+            return match find_io_error(&err).map(|e| e.kind()) {
+                Some(std::io::ErrorKind::NotFound) => ProxyError::SocketMissing(socket_path.to_string()),
+                _ => ProxyError::ConnectionRefused(socket_path.to_string()),
+            };
+        }
+        match find_hyper_error(&err) {
+            Some(e) if e.is_parse() || e.is_parse_status() || e.is_incomplete_message() => {
+                ProxyError::MalformedResponse(e.to_string())
+            }
+            Some(e) if e.is_timeout() => ProxyError::Timeout,
+            _ => ProxyError::Upstream(err.to_string()),
+        }
+    }
+
+    /// Classify a failure while reading the response body.
+    pub fn from_body(err: hyper::Error) -> Self {
+        if err.is_parse() || err.is_incomplete_message() {
+            ProxyError::MalformedResponse(err.to_string())
+        } else {
+            ProxyError::Upstream(err.to_string())
+        }
+    }
+}
+
+impl fmt::Display for ProxyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProxyError::InvalidRequest(e) => write!(f, "invalid request: {}", e),
+            ProxyError::Forbidden(req) => write!(f, "blocked by hecate:// access policy: {}", req),
+            ProxyError::NotGranted(req) => write!(f, "plugin is not granted access to {}", req),
+            ProxyError::PayloadTooLarge(limit) => write!(f, "request body exceeds the {} byte limit", limit),
+            ProxyError::TooManyRequests(target) => write!(f, "too many requests in flight to {}", target),
+            ProxyError::InvalidPluginName(e) => write!(f, "{}", e),
+            ProxyError::UnknownPlugin(name) => write!(f, "unknown plugin: {}", name),
+            ProxyError::SocketMissing(path) => write!(f, "daemon socket not found: {}", path),
+            ProxyError::ConnectionRefused(path) => write!(f, "daemon refused connection on {}", path),
+            ProxyError::Timeout => write!(f, "daemon did not respond in time"),
+            ProxyError::MalformedResponse(e) => write!(f, "malformed daemon response: {}", e),
+            ProxyError::Upstream(e) => write!(f, "daemon request failed: {}", e),
+            ProxyError::ReplayMiss(req) => write!(f, "no recorded response for {}", req),
+        }
+    }
+}
+
+impl std::error::Error for ProxyError {}
+
+impl From<tauri::http::Error> for ProxyError {
+    fn from(err: tauri::http::Error) -> Self {
+        ProxyError::InvalidRequest(err.to_string())
+    }
+}
+
+fn find_io_error<'a>(err: &'a (dyn std::error::Error + 'static)) -> Option<&'a std::io::Error> {
+    let mut source = Some(err);
+    while let Some(e) = source {
+        if let Some(io) = e.downcast_ref::<std::io::Error>() {
+            return Some(io);
+        }
+        source = e.source();
+    }
+    None
+}
+
+fn find_hyper_error<'a>(err: &'a (dyn std::error::Error + 'static)) -> Option<&'a hyper::Error> {
+    let mut source = Some(err);
+    while let Some(e) = source {
+        if let Some(h) = e.downcast_ref::<hyper::Error>() {
+            return Some(h);
+        }
+        source = e.source();
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_plugin_is_not_found() {
+        let response = ProxyError::UnknownPlugin("trader".to_string()).into_response();
+        assert_eq!(response.status(), 404);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["code"], "unknown_plugin");
+        assert!(response.headers().get("Retry-After").is_none());
+    }
+}
@@ -0,0 +1,192 @@
+//! Per-target concurrency limits for the `hecate://` proxy.
+//!
+//! Each target (the main daemon, or one plugin socket) has its own pool of
+//! slots, sized from the `concurrency` section of the proxy policy. A request
+//! holds its slot until the response body has been drained. When every slot
+//! is taken, a bounded number of requests wait for one; the rest, and any
+//! that wait too long, are refused with 429. A plugin polling in a tight
+//! loop therefore only ever competes with itself.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::proxy_error::ProxyError;
+use crate::proxy_policy;
+use crate::socket_proxy::Target;
+
+struct Slots {
+    semaphore: Arc<Semaphore>,
+    limit: usize,
+    waiting: AtomicUsize,
+}
+
+/// Slot pools by target. A pool is replaced when its configured limit
+/// changes; requests holding a slot in the old one finish undisturbed.
+static SLOTS: Mutex<Option<HashMap<Target, Arc<Slots>>>> = Mutex::new(None);
+
+/// A slot held for the lifetime of one proxied request.
+pub struct Permit {
+    _permit: OwnedSemaphorePermit,
+}
+
+/// Take a slot for `target`, waiting behind other requests if allowed.
+pub async fn acquire(target: &Target) -> Result<Permit, ProxyError> {
+    acquire_with(target, &proxy_policy::concurrency()).await
+}
+
+async fn acquire_with(target: &Target, limits: &proxy_policy::Concurrency) -> Result<Permit, ProxyError> {
+    let slots = slots_for(target, limits);
+
+    if let Ok(permit) = slots.semaphore.clone().try_acquire_owned() {
+        return Ok(Permit { _permit: permit });
+    }
+    // Counted until this request stops waiting, including when the webview
+    // cancels it and the future is dropped mid-wait.
+    let waiting = Waiting::enter(&slots.waiting);
+    if waiting.ahead >= limits.queue {
+        eprintln!("[proxy-limits] {} busy ({} in flight), refusing", target, slots.limit);
+        return Err(ProxyError::TooManyRequests(target.to_string()));
+    }
+    let waited = tokio::time::timeout(
+        Duration::from_millis(limits.queue_timeout_ms),
+        slots.semaphore.clone().acquire_owned(),
+    )
+    .await;
+    match waited {
+        Ok(Ok(permit)) => Ok(Permit { _permit: permit }),
+        _ => {
+            eprintln!("[proxy-limits] {} busy for {}ms, refusing", target, limits.queue_timeout_ms);
+            Err(ProxyError::TooManyRequests(target.to_string()))
+        }
+    }
+}
+
+/// One request counted in a pool's `waiting`, released on drop.
+struct Waiting<'a> {
+    counter: &'a AtomicUsize,
+    /// Requests already waiting when this one joined.
+    ahead: usize,
+}
+
+impl<'a> Waiting<'a> {
+    fn enter(counter: &'a AtomicUsize) -> Self {
+        let ahead = counter.fetch_add(1, Ordering::SeqCst);
+        Waiting { counter, ahead }
+    }
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.counter.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn slots_for(target: &Target, limits: &proxy_policy::Concurrency) -> Arc<Slots> {
+    let limit = match target {
+        Target::Daemon => limits.daemon,
+        Target::Plugin(_) => limits.per_plugin,
+    }
+    .max(1);
+    let mut slots = SLOTS.lock().unwrap_or_else(|e| e.into_inner());
+    let slots = slots.get_or_insert_with(HashMap::new);
+    match slots.get(target) {
+        Some(existing) if existing.limit == limit => existing.clone(),
+        _ => {
+            let fresh = Arc::new(Slots {
+                semaphore: Arc::new(Semaphore::new(limit)),
+                limit,
+                waiting: AtomicUsize::new(0),
+            });
+            slots.insert(target.clone(), fresh.clone());
+            fresh
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_name::PluginName;
+
+    fn plugin(name: &str) -> Target {
+        Target::Plugin(PluginName::parse(name).unwrap())
+    }
+
+    fn limits(per_plugin: usize, queue: usize, queue_timeout_ms: u64) -> proxy_policy::Concurrency {
+        proxy_policy::Concurrency {
+            per_plugin,
+            queue,
+            queue_timeout_ms,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn limit_is_per_target_and_freed_on_drop() {
+        let limits = limits(2, 0, 50);
+        let (a, b) = (plugin("limits-a"), plugin("limits-b"));
+        let first = acquire_with(&a, &limits).await.unwrap();
+        let _second = acquire_with(&a, &limits).await.unwrap();
+        // Full, with no queue: refused at once
+        assert!(matches!(acquire_with(&a, &limits).await, Err(ProxyError::TooManyRequests(_))));
+        // Another target has its own slots
+        let _other = acquire_with(&b, &limits).await.unwrap();
+
+        drop(first);
+        assert!(acquire_with(&a, &limits).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn queued_request_times_out_with_429() {
+        let limits = limits(1, 4, 30);
+        let target = plugin("limits-timeout");
+        let _held = acquire_with(&target, &limits).await.unwrap();
+
+        let err = match acquire_with(&target, &limits).await {
+            Err(e) => e,
+            Ok(_) => panic!("acquired a slot that is held"),
+        };
+        assert!(matches!(err, ProxyError::TooManyRequests(_)));
+        let response = err.into_response();
+        assert_eq!(response.status(), 429);
+        assert_eq!(response.headers().get("Retry-After").unwrap(), "1");
+        assert_eq!(slots_for(&target, &limits).waiting.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn queued_request_gets_the_freed_slot() {
+        let limits = limits(1, 4, 5_000);
+        let target = plugin("limits-queue");
+        let held = acquire_with(&target, &limits).await.unwrap();
+
+        let waiter = acquire_with(&target, &limits);
+        let release = async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            drop(held);
+        };
+        let (acquired, ()) = tokio::join!(waiter, release);
+        assert!(acquired.is_ok());
+        assert_eq!(slots_for(&target, &limits).waiting.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn cancelled_waiter_leaves_the_queue() {
+        let limits = limits(1, 1, 5_000);
+        let target = plugin("limits-cancel");
+        let _held = acquire_with(&target, &limits).await.unwrap();
+
+        // The webview cancelling a request drops its future mid-wait
+        for _ in 0..3 {
+            let cancelled = tokio::time::timeout(Duration::from_millis(20), acquire_with(&target, &limits)).await;
+            assert!(cancelled.is_err());
+            assert_eq!(slots_for(&target, &limits).waiting.load(Ordering::SeqCst), 0);
+        }
+        // The single queue place is free again: this one waits rather than
+        // being refused outright
+        let waited = tokio::time::timeout(Duration::from_millis(20), acquire_with(&target, &limits)).await;
+        assert!(waited.is_err());
+    }
+}
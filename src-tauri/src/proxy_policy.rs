@@ -0,0 +1,349 @@
+//! Declarative access policy for the `hecate://` protocol.
+//!
+//! Every proxied request is checked against an allowlist of method + path
+//! patterns for its target: the main daemon, or a plugin by name (falling
+//! back to the `"*"` plugin entry). Paths are matched as the target sees
+//! them: after the `/plugin/{name}` prefix has been stripped, or as-is for
+//! requests routed by a `{name}.plugin` host.
+//!
+//! The policy is read from `~/.hecate/config/web-proxy-policy.json` and
+//! reloaded by the config watcher when that file changes. A missing or
+//! invalid file falls back to the built-in default.
+//!
+//! Path patterns are `/`-separated; `*` matches within one segment and `**`
+//! matches any number of segments. A method of `"*"` matches every method.
+//!
+//! `max_body_bytes` caps request bodies (uploads); larger ones are refused
+//! with 413 before anything is sent to the daemon. Uploads are held in
+//! memory whole, so this is also the most one request can take.
+//!
+//! `concurrency` bounds requests in flight: `daemon` for the main daemon and
+//! `per_plugin` for each plugin socket. Up to `queue` more wait (at most
+//! `queue_timeout_ms`) for a slot; anything beyond gets 429 (see
+//! `proxy_limits`).
+
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{OnceLock, RwLock};
+
+use crate::config_watcher;
+use crate::socket_proxy::Target;
+
+pub const POLICY_FILE: &str = "web-proxy-policy.json";
+const POLICY_VERSION: u32 = 1;
+const DEFAULT_MAX_BODY_BYTES: u64 = 64 * 1024 * 1024;
+const DEFAULT_DAEMON_CONCURRENCY: usize = 32;
+const DEFAULT_PLUGIN_CONCURRENCY: usize = 8;
+const DEFAULT_QUEUE: usize = 64;
+const DEFAULT_QUEUE_TIMEOUT_MS: u64 = 10_000;
+
+const DEFAULT_POLICY: &str = r#"{
+    "version": 1,
+    "max_body_bytes": 67108864,
+    "concurrency": { "daemon": 32, "per_plugin": 8, "queue": 64, "queue_timeout_ms": 10000 },
+    "daemon": [
+        { "methods": ["GET", "POST", "PUT", "PATCH", "DELETE", "OPTIONS"], "paths": ["/api/**"] },
+        { "methods": ["GET"], "paths": ["/health"] }
+    ],
+    "plugins": {
+        "*": [
+            { "methods": ["*"], "paths": ["/**"] }
+        ]
+    }
+}"#;
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Policy {
+    pub version: u32,
+    #[serde(default = "default_max_body_bytes")]
+    pub max_body_bytes: u64,
+    #[serde(default)]
+    pub concurrency: Concurrency,
+    #[serde(default)]
+    pub daemon: Vec<Rule>,
+    #[serde(default)]
+    pub plugins: HashMap<String, Vec<Rule>>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Rule {
+    pub methods: Vec<String>,
+    pub paths: Vec<String>,
+}
+
+/// Concurrency limits per target.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct Concurrency {
+    /// Requests in flight to the main daemon.
+    pub daemon: usize,
+    /// Requests in flight to each plugin daemon.
+    pub per_plugin: usize,
+    /// Requests per target that may wait for a slot before 429.
+    pub queue: usize,
+    /// Longest a request waits for a slot before 429.
+    pub queue_timeout_ms: u64,
+}
+
+impl Default for Concurrency {
+    fn default() -> Self {
+        Concurrency {
+            daemon: DEFAULT_DAEMON_CONCURRENCY,
+            per_plugin: DEFAULT_PLUGIN_CONCURRENCY,
+            queue: DEFAULT_QUEUE,
+            queue_timeout_ms: DEFAULT_QUEUE_TIMEOUT_MS,
+        }
+    }
+}
+
+impl Rule {
+    pub fn allows(&self, method: &str, path: &str) -> bool {
+        self.methods.iter().any(|m| m == "*" || m.eq_ignore_ascii_case(method))
+            && self.paths.iter().any(|p| path_matches(p, path))
+    }
+}
+
+impl Policy {
+    /// Whether `method path` may be sent to `target`.
+    pub fn allows(&self, target: &Target, method: &str, path: &str) -> bool {
+        if has_dot_segment(path) {
+            return false;
+        }
+        let rules = match target {
+            Target::Daemon => Some(&self.daemon),
+            Target::Plugin(name) => self.plugins.get(name.as_str()).or_else(|| self.plugins.get("*")),
+        };
+        rules
+            .map(|rules| rules.iter().any(|r| r.allows(method, path)))
+            .unwrap_or(false)
+    }
+}
+
+static POLICY: OnceLock<RwLock<Policy>> = OnceLock::new();
+
+fn policy_path() -> PathBuf {
+    config_watcher::config_dir().join(POLICY_FILE)
+}
+
+fn default_max_body_bytes() -> u64 {
+    DEFAULT_MAX_BODY_BYTES
+}
+
+fn default_policy() -> Policy {
+    serde_json::from_str(DEFAULT_POLICY).expect("built-in policy is valid")
+}
+
+/// Load the policy file, falling back to the built-in default.
+fn load() -> Policy {
+    let path = policy_path();
+    let content = match std::fs::read_to_string(&path) {
+        Ok(c) => c,
+        Err(_) => return default_policy(),
+    };
+    match serde_json::from_str::<Policy>(&content) {
+        Ok(policy) if policy.version == POLICY_VERSION => {
+            eprintln!("[proxy-policy] loaded {}", path.display());
+            policy
+        }
+        Ok(policy) => {
+            eprintln!(
+                "[proxy-policy] unsupported policy version {} in {}, using built-in default",
+                policy.version,
+                path.display()
+            );
+            default_policy()
+        }
+        Err(e) => {
+            eprintln!("[proxy-policy] invalid {}: {}, using built-in default", path.display(), e);
+            default_policy()
+        }
+    }
+}
+
+fn policy() -> &'static RwLock<Policy> {
+    POLICY.get_or_init(|| RwLock::new(load()))
+}
+
+/// Re-read the policy file. Called by the config watcher on change.
+pub fn reload() {
+    let fresh = load();
+    if let Ok(mut current) = policy().write() {
+        *current = fresh;
+    }
+}
+
+/// Check a request against the active policy.
+pub fn check(target: &Target, method: &str, path: &str) -> bool {
+    let allowed = policy()
+        .read()
+        .map(|p| p.allows(target, method, path))
+        .unwrap_or(false);
+    if !allowed {
+        eprintln!("[proxy-policy] denied {} {} on {}", method, path, target);
+    }
+    allowed
+}
+
+/// Largest request body the proxy forwards.
+pub fn max_body_bytes() -> u64 {
+    policy()
+        .read()
+        .map(|p| p.max_body_bytes)
+        .unwrap_or(DEFAULT_MAX_BODY_BYTES)
+}
+
+/// Concurrency limits for the proxy.
+pub fn concurrency() -> Concurrency {
+    policy()
+        .read()
+        .map(|p| p.concurrency.clone())
+        .unwrap_or_default()
+}
+
+/// Match a `/`-separated path against a pattern with `*` and `**` wildcards.
+pub fn path_matches(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<&str> = pattern.trim_start_matches('/').split('/').collect();
+    let path: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+    segments_match(&pattern, &path)
+}
+
+fn segments_match(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| segments_match(rest, &path[skip..])),
+        Some((seg, rest)) => match path.split_first() {
+            Some((p, path_rest)) => glob_segment(seg, p) && segments_match(rest, path_rest),
+            None => false,
+        },
+    }
+}
+
+/// Match one segment where `*` stands for any run of characters.
+fn glob_segment(pattern: &str, segment: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    if parts.len() == 1 {
+        return pattern == segment;
+    }
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if !segment.starts_with(first) || !segment[first.len()..].ends_with(last) {
+        return false;
+    }
+    let mut rest = &segment[first.len()..segment.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(pos) => rest = &rest[pos + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+/// Reject `.`/`..` segments (also percent-encoded) so a pattern like
+/// `/api/**` cannot be escaped by path traversal on the daemon side.
+fn has_dot_segment(path: &str) -> bool {
+    path.split('/').any(|seg| {
+        let decoded = seg.to_ascii_lowercase().replace("%2e", ".");
+        decoded == "." || decoded == ".."
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_patterns() {
+        let cases: &[(&str, &str, bool)] = &[
+            // Exact
+            ("/health", "/health", true),
+            ("/health", "/healthz", false),
+            ("/health", "/health/", false),
+            // `**` spans any number of segments, including none
+            ("/api/**", "/api", true),
+            ("/api/**", "/api/", true),
+            ("/api/**", "/api/settings", true),
+            ("/api/**", "/api/realms/join/status", true),
+            ("/api/**/status", "/api/status", true),
+            ("/api/**/status", "/api/realms/join/status", true),
+            ("/api/**/status", "/api/realms/join/statuses", false),
+            ("/**", "/", true),
+            ("/**", "/anything/at/all", true),
+            // `*` stays within one segment
+            ("/api/*", "/api/settings", true),
+            ("/api/*", "/api/", true),
+            ("/api/*", "/api", false),
+            ("/api/*", "/api/realms/join", false),
+            ("/api/*/status", "/api/join/status", true),
+            ("/api/*/status", "/api/realms/join/status", false),
+            // Prefix traps
+            ("/api", "/apix", false),
+            ("/api/**", "/apix", false),
+            ("/api/**", "/apix/settings", false),
+            ("/api/*", "/apix/settings", false),
+            ("/plugin/trader/**", "/plugin/traderx/api", false),
+            // Leading slash is optional on either side
+            ("api/*", "/api/settings", true),
+            ("/api/*", "api/settings", true),
+        ];
+        for (pattern, path, expected) in cases {
+            assert_eq!(path_matches(pattern, path), *expected, "{} vs {}", pattern, path);
+        }
+    }
+
+    #[test]
+    fn segment_globs() {
+        let cases: &[(&str, &str, bool)] = &[
+            ("*", "", true),
+            ("*", "anything", true),
+            ("settings", "settings", true),
+            ("settings", "setting", false),
+            ("v*", "v1", true),
+            ("v*", "x1", false),
+            ("*.json", "manifest.json", true),
+            ("*.json", "manifest.jsonx", false),
+            ("a*b*c", "abc", true),
+            ("a*b*c", "aXXbYYc", true),
+            ("a*b*c", "acb", false),
+            // Prefix and suffix may not overlap
+            ("ab*ba", "aba", false),
+            ("ab*ba", "abba", true),
+            ("**", "x", true),
+        ];
+        for (pattern, segment, expected) in cases {
+            assert_eq!(glob_segment(pattern, segment), *expected, "{} vs {}", pattern, segment);
+        }
+    }
+
+    #[test]
+    fn dot_segments() {
+        let cases: &[(&str, bool)] = &[
+            ("/api/settings", false),
+            ("/api/../plugin/x", true),
+            ("/api/./settings", true),
+            ("/api/..", true),
+            ("/api/%2e%2e/plugin/x", true),
+            ("/api/%2E%2E/plugin/x", true),
+            ("/api/.%2e/plugin/x", true),
+            ("/api/%2e./plugin/x", true),
+            ("/api/%2e", true),
+            ("/api/...", false),
+            ("/api/..hidden", false),
+            ("/api/a..b", false),
+            ("/api/.well-known", false),
+            // Double-encoded: the daemon decodes it once, to `%2e`
+            ("/api/%252e%252e", false),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(has_dot_segment(path), *expected, "{}", path);
+        }
+    }
+
+    #[test]
+    fn dot_segments_are_denied_even_under_a_wildcard() {
+        let policy = default_policy();
+        assert!(policy.allows(&Target::Daemon, "GET", "/api/settings"));
+        assert!(!policy.allows(&Target::Daemon, "GET", "/api/%2e%2e/plugin/x/secret"));
+        assert!(!policy.allows(&Target::Daemon, "GET", "/api/../health"));
+    }
+}
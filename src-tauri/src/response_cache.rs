@@ -0,0 +1,625 @@
+//! Offline-capable response cache for idempotent `hecate://` GETs.
+//!
+//! Successful GET responses are kept under `~/.hecate/hecate-web/cache/`
+//! (one `.json` metadata file and one `.body` file per entry). While the
+//! daemon is up, a cached entry is revalidated with `If-None-Match` /
+//! `If-Modified-Since`; a `304` is answered from the cache. While the daemon
+//! is unavailable (socket gone, or `daemon_watcher` reports no health), the
+//! cached copy is served as-is with `X-Hecate-Stale: true`, so the UI keeps
+//! rendering the last known state instead of a wall of 503s.
+//!
+//! Entries are dropped when a write (POST/PUT/PATCH/DELETE) to the same path
+//! succeeds, and when `daemon_streaming` sees an event that changes the data
+//! behind them (see [`EVENT_INVALIDATIONS`]).
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::http::header::{
+    AGE, CACHE_CONTROL, CONTENT_LENGTH, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH,
+    LAST_MODIFIED, RANGE, VARY,
+};
+use tauri::http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+
+use crate::config_watcher;
+use crate::daemon_profiles;
+use crate::proxy_policy;
+use crate::socket_proxy::Target;
+
+const CACHE_DIR: &str = "cache";
+/// Largest response body that is worth keeping.
+const MAX_ENTRY_SIZE: usize = 4 * 1024 * 1024;
+/// Entries kept before the oldest are evicted.
+const MAX_ENTRIES: usize = 512;
+
+/// Daemon event types and the daemon paths whose cached responses they make
+/// out of date. An identity change rescopes every API, so it clears all, as
+/// does a gap in the event stream (the missed events could be anything).
+const EVENT_INVALIDATIONS: &[(&str, &[&str])] = &[
+    ("settings_changed", &["/api/settings/**", "/api/config/**"]),
+    ("realm_join_status", &["/api/realms/**"]),
+    ("identity_changed", &["/**"]),
+    ("stream_gap", &["/**"]),
+];
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Meta {
+    key: String,
+    target: String,
+    path: String,
+    status: u16,
+    headers: Vec<(String, String)>,
+    /// Request header values named by the response `Vary`, at store time.
+    vary: Vec<(String, Option<String>)>,
+    stored_at: u64,
+}
+
+/// Index of cached entries by key, loaded from disk on first use.
+static INDEX: Mutex<Option<HashMap<String, Meta>>> = Mutex::new(None);
+
+fn with_index<T>(f: impl FnOnce(&mut HashMap<String, Meta>) -> T) -> T {
+    let mut guard = INDEX.lock().unwrap_or_else(|e| e.into_inner());
+    f(guard.get_or_insert_with(load_index))
+}
+
+/// A cached response ready to be served or revalidated.
+pub struct Cached {
+    meta: Meta,
+    body: Vec<u8>,
+}
+
+impl Cached {
+    pub fn status(&self) -> StatusCode {
+        StatusCode::from_u16(self.meta.status).unwrap_or(StatusCode::OK)
+    }
+
+    pub fn headers(&self) -> HeaderMap {
+        to_header_map(&self.meta.headers)
+    }
+
+    pub fn into_body(self) -> Vec<u8> {
+        self.body
+    }
+
+    /// Seconds since the response was stored.
+    pub fn age(&self) -> u64 {
+        now_secs().saturating_sub(self.meta.stored_at)
+    }
+
+    /// Headers for serving this entry while the target is unreachable:
+    /// the stored ones plus `Age` and `X-Hecate-Stale: true`.
+    pub fn stale_headers(&self) -> HeaderMap {
+        let mut headers = self.headers();
+        headers.insert(AGE, HeaderValue::from(self.age()));
+        headers.insert("x-hecate-stale", HeaderValue::from_static("true"));
+        headers
+    }
+
+    /// Add validators for a conditional request, unless the caller already
+    /// sent its own (in which case its 304 is passed through untouched).
+    /// Returns whether the request is now a revalidation of this entry.
+    pub fn add_validators(&self, request_headers: &mut HeaderMap) -> bool {
+        if request_headers.contains_key(IF_NONE_MATCH) || request_headers.contains_key(IF_MODIFIED_SINCE) {
+            return false;
+        }
+        let headers = self.headers();
+        let mut added = false;
+        if let Some(etag) = headers.get(ETAG) {
+            request_headers.insert(IF_NONE_MATCH, etag.clone());
+            added = true;
+        }
+        if let Some(modified) = headers.get(LAST_MODIFIED) {
+            request_headers.insert(IF_MODIFIED_SINCE, modified.clone());
+            added = true;
+        }
+        added
+    }
+}
+
+/// Cache key for a GET on `target`, or `None` when the request must bypass
+/// the cache (not a GET, a Range request, or `Cache-Control: no-store`).
+pub fn key(target: &Target, method: &tauri::http::Method, path_and_query: &str, headers: &HeaderMap) -> Option<String> {
+    if method != tauri::http::Method::GET || headers.contains_key(RANGE) || has_directive(headers, "no-store") {
+        return None;
+    }
+    // Daemon responses are kept apart per connection profile
+    match target {
+        Target::Daemon => Some(format!("{}|{}|{}", daemon_profiles::active_name(), target, path_and_query)),
+        Target::Plugin(_) => Some(format!("{}|{}", target, path_and_query)),
+    }
+}
+
+/// Cached response for `key` whose `Vary` headers match this request.
+pub fn lookup(key: &str, request_headers: &HeaderMap) -> Option<Cached> {
+    let meta = with_index(|index| index.get(key).cloned())?;
+    let vary_matches = meta
+        .vary
+        .iter()
+        .all(|(name, value)| header_str(request_headers, name) == value.as_deref());
+    if !vary_matches {
+        return None;
+    }
+    match std::fs::read(entry_path(key, "body")) {
+        Ok(body) => Some(Cached { meta, body }),
+        Err(_) => {
+            remove(key);
+            None
+        }
+    }
+}
+
+/// Merge the headers of a `304 Not Modified` into the stored entry and
+/// return the refreshed entry.
+pub fn refresh(mut cached: Cached, not_modified: &HeaderMap) -> Cached {
+    for (name, _) in not_modified.iter() {
+        if name == CONTENT_LENGTH || name == CONTENT_TYPE {
+            continue;
+        }
+        cached.meta.headers.retain(|(k, _)| !k.eq_ignore_ascii_case(name.as_str()));
+    }
+    for (name, value) in not_modified.iter() {
+        if name == CONTENT_LENGTH || name == CONTENT_TYPE {
+            continue;
+        }
+        if let Ok(v) = value.to_str() {
+            cached.meta.headers.push((name.as_str().to_string(), v.to_string()));
+        }
+    }
+    cached.meta.stored_at = now_secs();
+    write_meta(&cached.meta);
+    with_index(|index| index.insert(cached.meta.key.clone(), cached.meta.clone()));
+    cached
+}
+
+/// Response body being collected for the cache as it streams to the webview.
+/// Nothing is stored unless [`Pending::commit`] is reached with the whole body.
+pub struct Pending {
+    meta: Meta,
+    body: Vec<u8>,
+    too_large: bool,
+}
+
+/// Start storing a response, if it is cacheable.
+pub fn store(
+    key: String,
+    target: &Target,
+    path: &str,
+    request_headers: &HeaderMap,
+    status: StatusCode,
+    response_headers: &HeaderMap,
+) -> Option<Pending> {
+    if status != StatusCode::OK || has_directive(response_headers, "no-store") {
+        return None;
+    }
+    let content_type = header_str(response_headers, CONTENT_TYPE.as_str()).unwrap_or("");
+    if content_type.starts_with("text/event-stream") {
+        return None;
+    }
+    let declared_len = header_str(response_headers, CONTENT_LENGTH.as_str()).and_then(|v| v.parse::<usize>().ok());
+    if declared_len.is_some_and(|len| len > MAX_ENTRY_SIZE) {
+        return None;
+    }
+
+    let mut vary = Vec::new();
+    for value in response_headers.get_all(VARY).iter().filter_map(|v| v.to_str().ok()) {
+        for name in value.split(',').map(|n| n.trim().to_ascii_lowercase()) {
+            if name == "*" {
+                return None;
+            }
+            if !name.is_empty() {
+                let value = header_str(request_headers, &name).map(|v| v.to_string());
+                vary.push((name, value));
+            }
+        }
+    }
+
+    Some(Pending {
+        meta: Meta {
+            key,
+            target: target.to_string(),
+            path: path.to_string(),
+            status: status.as_u16(),
+            headers: response_headers
+                .iter()
+                .filter_map(|(k, v)| Some((k.as_str().to_string(), v.to_str().ok()?.to_string())))
+                .collect(),
+            vary,
+            stored_at: now_secs(),
+        },
+        body: Vec::with_capacity(declared_len.unwrap_or(0)),
+        too_large: false,
+    })
+}
+
+impl Pending {
+    pub fn push(&mut self, chunk: &[u8]) {
+        if self.too_large || self.body.len() + chunk.len() > MAX_ENTRY_SIZE {
+            self.too_large = true;
+            self.body = Vec::new();
+            return;
+        }
+        self.body.extend_from_slice(chunk);
+    }
+
+    /// Persist the collected response (the body has been read to the end).
+    pub fn commit(self) {
+        if self.too_large {
+            return;
+        }
+        let dir = cache_dir();
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            eprintln!("[response-cache] cannot create {}: {}", dir.display(), e);
+            return;
+        }
+        if let Err(e) = std::fs::write(entry_path(&self.meta.key, "body"), &self.body) {
+            eprintln!("[response-cache] write failed for {}: {}", self.meta.key, e);
+            return;
+        }
+        write_meta(&self.meta);
+
+        let evicted = with_index(|index| {
+            index.insert(self.meta.key.clone(), self.meta);
+            let mut evicted = Vec::new();
+            while index.len() > MAX_ENTRIES {
+                let oldest = index
+                    .values()
+                    .min_by_key(|m| m.stored_at)
+                    .map(|m| m.key.clone());
+                match oldest {
+                    Some(k) => {
+                        index.remove(&k);
+                        evicted.push(k);
+                    }
+                    None => break,
+                }
+            }
+            evicted
+        });
+        for key in evicted {
+            remove_files(&key);
+        }
+    }
+}
+
+/// Drop cached responses for `path` on `target` after a successful write.
+pub fn invalidate_path(target: &Target, path: &str) {
+    let target = target.to_string();
+    invalidate(|meta| meta.target == target && meta.path == path);
+}
+
+/// Drop daemon responses made stale by a daemon event, if the event type
+/// has any invalidation rules.
+pub fn invalidate_for_event(event_type: &str) {
+    let Some((_, patterns)) = EVENT_INVALIDATIONS.iter().find(|(t, _)| *t == event_type) else {
+        return;
+    };
+    let daemon = Target::Daemon.to_string();
+    let count = invalidate(|meta| {
+        meta.target == daemon && patterns.iter().any(|p| proxy_policy::path_matches(p, &meta.path))
+    });
+    if count > 0 {
+        eprintln!("[response-cache] {} invalidated {} entries", event_type, count);
+    }
+}
+
+fn invalidate(matches: impl Fn(&Meta) -> bool) -> usize {
+    let keys: Vec<String> = with_index(|index| {
+        let keys: Vec<String> = index.values().filter(|m| matches(m)).map(|m| m.key.clone()).collect();
+        for key in &keys {
+            index.remove(key);
+        }
+        keys
+    });
+    for key in &keys {
+        remove_files(key);
+    }
+    keys.len()
+}
+
+fn remove(key: &str) {
+    with_index(|index| index.remove(key));
+    remove_files(key);
+}
+
+fn remove_files(key: &str) {
+    let _ = std::fs::remove_file(entry_path(key, "json"));
+    let _ = std::fs::remove_file(entry_path(key, "body"));
+}
+
+fn cache_dir() -> PathBuf {
+    config_watcher::state_dir().join(CACHE_DIR)
+}
+
+/// `{cache_dir}/{fnv1a(key)}.{ext}` — keys hold `/`, `?` and arbitrary query
+/// text, so files are named by a hash; the key itself lives in the metadata.
+fn entry_path(key: &str, ext: &str) -> PathBuf {
+    let hash = key
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325u64, |h, b| (h ^ b as u64).wrapping_mul(0x0100_0000_01b3));
+    cache_dir().join(format!("{:016x}.{}", hash, ext))
+}
+
+fn write_meta(meta: &Meta) {
+    match serde_json::to_vec(meta) {
+        Ok(json) => {
+            if let Err(e) = std::fs::write(entry_path(&meta.key, "json"), json) {
+                eprintln!("[response-cache] write failed for {}: {}", meta.key, e);
+            }
+        }
+        Err(e) => eprintln!("[response-cache] serialize failed for {}: {}", meta.key, e),
+    }
+}
+
+fn load_index() -> HashMap<String, Meta> {
+    let mut index = HashMap::new();
+    let Ok(entries) = std::fs::read_dir(cache_dir()) else {
+        return index;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().map(|e| e != "json").unwrap_or(true) {
+            continue;
+        }
+        let meta = std::fs::read(&path)
+            .ok()
+            .and_then(|data| serde_json::from_slice::<Meta>(&data).ok());
+        match meta {
+            Some(meta) => {
+                index.insert(meta.key.clone(), meta);
+            }
+            None => {
+                let _ = std::fs::remove_file(&path);
+                let _ = std::fs::remove_file(path.with_extension("body"));
+            }
+        }
+    }
+    eprintln!("[response-cache] loaded {} cached responses", index.len());
+    index
+}
+
+fn has_directive(headers: &HeaderMap, directive: &str) -> bool {
+    headers
+        .get_all(CACHE_CONTROL)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|d| d.trim().eq_ignore_ascii_case(directive))
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+fn to_header_map(pairs: &[(String, String)]) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (name, value) in pairs {
+        if let (Ok(n), Ok(v)) = (HeaderName::from_bytes(name.as_bytes()), HeaderValue::from_str(value)) {
+            headers.append(n, v);
+        }
+    }
+    headers
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_name::PluginName;
+    use tauri::http::Method;
+
+    /// The index is process-wide; tests take turns and start from empty.
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    fn fresh() -> std::sync::MutexGuard<'static, ()> {
+        let guard = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        config_watcher::test_home();
+        invalidate(|_| true);
+        guard
+    }
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(*name, HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    fn daemon_key(path: &str) -> String {
+        key(&Target::Daemon, &Method::GET, path, &HeaderMap::new()).unwrap()
+    }
+
+    fn cache(target: &Target, key: &str, path: &str, response_headers: &HeaderMap, body: &[u8]) {
+        let mut pending = store(key.to_string(), target, path, &HeaderMap::new(), StatusCode::OK, response_headers)
+            .expect("cacheable");
+        pending.push(body);
+        pending.commit();
+    }
+
+    #[test]
+    fn only_plain_gets_have_keys() {
+        let _guard = fresh();
+        let daemon = Target::Daemon;
+        assert!(key(&daemon, &Method::GET, "/api/x", &HeaderMap::new()).is_some());
+        assert!(key(&daemon, &Method::POST, "/api/x", &HeaderMap::new()).is_none());
+        assert!(key(&daemon, &Method::GET, "/api/x", &headers(&[("range", "bytes=0-1")])).is_none());
+        assert!(key(&daemon, &Method::GET, "/api/x", &headers(&[("cache-control", "no-store")])).is_none());
+    }
+
+    #[test]
+    fn daemon_keys_are_scoped_to_the_profile() {
+        let _guard = fresh();
+        assert_eq!(daemon_key("/api/x"), format!("{}|daemon|/api/x", daemon_profiles::active_name()));
+        let plugin = Target::Plugin(PluginName::parse("demo").unwrap());
+        assert_eq!(
+            key(&plugin, &Method::GET, "/api/x", &HeaderMap::new()).unwrap(),
+            format!("{}|/api/x", plugin)
+        );
+
+        // What another profile cached is not served under this one
+        cache(&Target::Daemon, "elsewhere|daemon|/api/x", "/api/x", &HeaderMap::new(), b"theirs");
+        assert!(lookup(&daemon_key("/api/x"), &HeaderMap::new()).is_none());
+    }
+
+    #[test]
+    fn revalidates_with_the_stored_validators() {
+        let _guard = fresh();
+        let key = daemon_key("/api/settings");
+        let stored = headers(&[("etag", "\"v1\""), ("last-modified", "Tue, 01 Sep 2026 10:00:00 GMT")]);
+        cache(&Target::Daemon, &key, "/api/settings", &stored, b"{\"a\":1}");
+
+        let cached = lookup(&key, &HeaderMap::new()).unwrap();
+        let mut request = HeaderMap::new();
+        assert!(cached.add_validators(&mut request));
+        assert_eq!(request[IF_NONE_MATCH], "\"v1\"");
+        assert_eq!(request[IF_MODIFIED_SINCE], "Tue, 01 Sep 2026 10:00:00 GMT");
+
+        // The caller's own validators are left to the daemon
+        let mut own = headers(&[("if-none-match", "\"mine\"")]);
+        assert!(!cached.add_validators(&mut own));
+        assert_eq!(own[IF_NONE_MATCH], "\"mine\"");
+
+        // A 304 refreshes the headers and keeps the body
+        let refreshed = refresh(cached, &headers(&[("etag", "\"v2\""), ("content-length", "0")]));
+        assert_eq!(refreshed.headers()[ETAG], "\"v2\"");
+        assert_eq!(refreshed.into_body(), b"{\"a\":1}");
+        assert_eq!(lookup(&key, &HeaderMap::new()).unwrap().headers()[ETAG], "\"v2\"");
+    }
+
+    #[test]
+    fn vary_headers_must_match() {
+        let _guard = fresh();
+        let key = daemon_key("/api/list");
+        let request = headers(&[("accept-language", "en")]);
+        let mut pending = store(
+            key.clone(),
+            &Target::Daemon,
+            "/api/list",
+            &request,
+            StatusCode::OK,
+            &headers(&[("vary", "Accept-Language")]),
+        )
+        .unwrap();
+        pending.push(b"[]");
+        pending.commit();
+
+        assert!(lookup(&key, &request).is_some());
+        assert!(lookup(&key, &headers(&[("accept-language", "de")])).is_none());
+        assert!(store(key, &Target::Daemon, "/", &request, StatusCode::OK, &headers(&[("vary", "*")])).is_none());
+    }
+
+    #[test]
+    fn oversized_and_uncacheable_responses_are_not_kept() {
+        let _guard = fresh();
+        let key = daemon_key("/api/big");
+        let none = HeaderMap::new();
+        let start = |status, response_headers: &HeaderMap| {
+            store(key.clone(), &Target::Daemon, "/api/big", &none, status, response_headers)
+        };
+        assert!(start(StatusCode::NOT_FOUND, &none).is_none());
+        assert!(start(StatusCode::OK, &headers(&[("cache-control", "no-store")])).is_none());
+        assert!(start(StatusCode::OK, &headers(&[("content-type", "text/event-stream")])).is_none());
+        let mut too_long = HeaderMap::new();
+        too_long.insert(CONTENT_LENGTH, HeaderValue::from(MAX_ENTRY_SIZE + 1));
+        assert!(start(StatusCode::OK, &too_long).is_none());
+
+        // Undeclared length: dropped once the body passes the cap
+        let mut pending = start(StatusCode::OK, &none).unwrap();
+        pending.push(&vec![0; MAX_ENTRY_SIZE]);
+        pending.push(b"x");
+        pending.commit();
+        assert!(lookup(&key, &none).is_none());
+    }
+
+    #[test]
+    fn oldest_entries_are_evicted_past_the_cap() {
+        let _guard = fresh();
+        let none = HeaderMap::new();
+        let first = daemon_key("/api/n/0");
+        let mut pending = store(first.clone(), &Target::Daemon, "/api/n/0", &none, StatusCode::OK, &none).unwrap();
+        pending.meta.stored_at = 0;
+        pending.commit();
+        for n in 1..=MAX_ENTRIES {
+            let path = format!("/api/n/{}", n);
+            cache(&Target::Daemon, &daemon_key(&path), &path, &none, b"{}");
+        }
+
+        assert_eq!(with_index(|index| index.len()), MAX_ENTRIES);
+        assert!(lookup(&first, &none).is_none());
+        assert!(!entry_path(&first, "body").exists());
+        assert!(lookup(&daemon_key("/api/n/1"), &none).is_some());
+    }
+
+    #[test]
+    fn writes_and_events_invalidate() {
+        let _guard = fresh();
+        let none = HeaderMap::new();
+        let plugin = Target::Plugin(PluginName::parse("demo").unwrap());
+        for path in ["/api/settings/theme", "/api/realms/1", "/api/agents"] {
+            cache(&Target::Daemon, &daemon_key(path), path, &none, b"{}");
+        }
+        let plugin_key = key(&plugin, &Method::GET, "/api/agents", &none).unwrap();
+        cache(&plugin, &plugin_key, "/api/agents", &none, b"{}");
+
+        // A write drops that path on that target only
+        invalidate_path(&Target::Daemon, "/api/agents");
+        assert!(lookup(&daemon_key("/api/agents"), &none).is_none());
+        assert!(lookup(&plugin_key, &none).is_some());
+
+        invalidate_for_event("settings_changed");
+        assert!(lookup(&daemon_key("/api/settings/theme"), &none).is_none());
+        assert!(lookup(&daemon_key("/api/realms/1"), &none).is_some());
+
+        invalidate_for_event("unrelated_event");
+        assert!(lookup(&daemon_key("/api/realms/1"), &none).is_some());
+
+        // Events only ever concern the daemon's responses
+        invalidate_for_event("stream_gap");
+        assert!(lookup(&daemon_key("/api/realms/1"), &none).is_none());
+        assert!(lookup(&plugin_key, &none).is_some());
+    }
+
+    #[test]
+    fn stale_copy_is_marked() {
+        let _guard = fresh();
+        let key = daemon_key("/api/status");
+        let mut pending = store(
+            key.clone(),
+            &Target::Daemon,
+            "/api/status",
+            &HeaderMap::new(),
+            StatusCode::OK,
+            &headers(&[("content-type", "application/json")]),
+        )
+        .unwrap();
+        pending.meta.stored_at = now_secs() - 90;
+        pending.push(b"{}");
+        pending.commit();
+
+        let stale = lookup(&key, &HeaderMap::new()).unwrap().stale_headers();
+        assert_eq!(stale["x-hecate-stale"], "true");
+        assert_eq!(stale[CONTENT_TYPE], "application/json");
+        let age: u64 = stale[AGE].to_str().unwrap().parse().unwrap();
+        assert!((90..100).contains(&age), "{}", age);
+    }
+
+    #[test]
+    fn entries_survive_a_reload() {
+        let _guard = fresh();
+        let key = daemon_key("/api/persisted");
+        cache(&Target::Daemon, &key, "/api/persisted", &headers(&[("etag", "\"p\"")]), b"kept");
+        *INDEX.lock().unwrap() = None;
+
+        let cached = lookup(&key, &HeaderMap::new()).unwrap();
+        assert_eq!(cached.headers()[ETAG], "\"p\"");
+        assert_eq!(cached.into_body(), b"kept");
+    }
+}
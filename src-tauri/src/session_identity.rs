@@ -0,0 +1,229 @@
+//! The user identity the shell vouches for on proxied requests.
+//!
+//! The daemon scopes its APIs by `x-hecate-user-id`. That header is not taken
+//! from the webview: any plugin component could set it and act as another
+//! user. Instead the shell learns the identity itself, from the daemon's
+//! settings API (`identity.hecate_user_id`) and from `identity_changed`
+//! events, strips whatever the webview sent, and sets the authoritative value
+//! on every request it forwards to the daemon or a plugin daemon.
+//!
+//! The identity is fetched when the daemon becomes healthy and forgotten when
+//! the connection profile switches to another daemon.
+
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::http::{HeaderMap, HeaderValue, Method};
+
+use crate::correlation;
+use crate::daemon_client;
+use crate::socket_proxy;
+use crate::traffic;
+
+pub const USER_ID_HEADER: &str = "x-hecate-user-id";
+const IDENTITY_PATH: &str = "/api/settings";
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+static USER_ID: Mutex<Option<String>> = Mutex::new(None);
+
+/// Replace any client-supplied user id with the one the shell knows. Without
+/// a known identity the header is only removed.
+pub fn apply(headers: &mut HeaderMap) {
+    headers.remove(USER_ID_HEADER);
+    let user_id = USER_ID.lock().ok().and_then(|id| id.clone());
+    if let Some(value) = user_id.and_then(|id| HeaderValue::from_str(&id).ok()) {
+        headers.insert(USER_ID_HEADER, value);
+    }
+}
+
+/// Drop the identity; the next daemon may belong to someone else.
+pub fn forget() {
+    set(None);
+}
+
+/// Learn the identity from an `identity_changed` payload, or re-read it from
+/// the daemon if the payload does not carry one.
+pub fn on_identity_changed(data: &serde_json::Value) {
+    match user_id_in(data) {
+        Some(id) => set(Some(id)),
+        None => {
+            tauri::async_runtime::spawn(async {
+                refresh().await;
+            });
+        }
+    }
+}
+
+/// Fetch the identity from the daemon's settings API.
+pub async fn refresh() {
+    refresh_from(&socket_proxy::resolve_socket_path()).await;
+}
+
+/// Fetch the identity from the settings API on `socket_path`. A failed
+/// fetch keeps the identity already known.
+async fn refresh_from(socket_path: &str) {
+    match fetch(socket_path).await {
+        Ok(id) => set(id),
+        Err(e) => eprintln!("[session-identity] could not read identity: {}", e),
+    }
+}
+
+async fn fetch(socket_path: &str) -> Result<Option<String>, String> {
+    let mut request = daemon_client::request(Method::GET, socket_path, IDENTITY_PATH)
+        .body(daemon_client::empty())
+        .map_err(|e| e.to_string())?;
+    correlation::Ids::generate().apply(request.headers_mut());
+    traffic::record_tx(daemon_client::head_len(&request) as u64);
+
+    let response = tokio::time::timeout(FETCH_TIMEOUT, daemon_client::send(request))
+        .await
+        .map_err(|_| "timed out".to_string())?
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("daemon returned {}", response.status().as_u16()));
+    }
+    let buf = daemon_client::read_to_end(&mut response.into_body(), FETCH_TIMEOUT)
+        .await
+        .map_err(|e| e.to_string())?;
+    traffic::record_rx(buf.len() as u64);
+    let settings: serde_json::Value = serde_json::from_slice(&buf).map_err(|e| e.to_string())?;
+    Ok(user_id_in(&settings))
+}
+
+/// `hecate_user_id` at the top level or under `identity`.
+fn user_id_in(value: &serde_json::Value) -> Option<String> {
+    value
+        .get("hecate_user_id")
+        .or_else(|| value.get("identity").and_then(|i| i.get("hecate_user_id")))
+        .and_then(|id| id.as_str())
+        .filter(|id| !id.is_empty())
+        .map(str::to_string)
+}
+
+fn set(user_id: Option<String>) {
+    if let Ok(mut current) = USER_ID.lock() {
+        if *current != user_id {
+            match &user_id {
+                Some(id) => eprintln!("[session-identity] acting as {}", id),
+                None => eprintln!("[session-identity] no identity"),
+            }
+            *current = user_id;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_watcher;
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixListener;
+
+    /// The identity is process-wide; tests take turns.
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    fn serial() -> std::sync::MutexGuard<'static, ()> {
+        let guard = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        forget();
+        guard
+    }
+
+    fn sent_user_id(client_value: Option<&'static str>) -> Vec<String> {
+        let mut headers = HeaderMap::new();
+        if let Some(value) = client_value {
+            headers.append(USER_ID_HEADER, HeaderValue::from_static(value));
+            headers.append(USER_ID_HEADER, HeaderValue::from_static("also-me"));
+        }
+        apply(&mut headers);
+        headers
+            .get_all(USER_ID_HEADER)
+            .iter()
+            .map(|v| v.to_str().unwrap().to_string())
+            .collect()
+    }
+
+    /// A daemon on its own socket answering every request with `status` and `body`.
+    fn settings_daemon(name: &str, status: &'static str, body: &'static str) -> String {
+        let path = config_watcher::test_home().join(name);
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap_or(0) > 0 && line != "\r\n" {
+                    line.clear();
+                }
+                let mut stream = stream;
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+            }
+        });
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn client_supplied_user_id_is_never_forwarded() {
+        let _guard = serial();
+        assert!(sent_user_id(Some("someone-else")).is_empty());
+        assert!(sent_user_id(None).is_empty());
+
+        set(Some("me".to_string()));
+        assert_eq!(sent_user_id(Some("someone-else")), vec!["me"]);
+        assert_eq!(sent_user_id(None), vec!["me"]);
+    }
+
+    #[test]
+    fn identity_is_read_from_settings() {
+        let _guard = serial();
+        let socket = settings_daemon(
+            "identity-ok.sock",
+            "200 OK",
+            r#"{"theme":"dark","identity":{"hecate_user_id":"u-settings"}}"#,
+        );
+        daemon_client::block_on(refresh_from(&socket));
+        assert_eq!(sent_user_id(Some("forged")), vec!["u-settings"]);
+
+        // A daemon that cannot answer leaves the known identity alone
+        let failing = settings_daemon("identity-down.sock", "500 Internal Server Error", "{}");
+        daemon_client::block_on(refresh_from(&failing));
+        assert_eq!(sent_user_id(None), vec!["u-settings"]);
+    }
+
+    #[test]
+    fn identity_changed_events_carry_the_new_id() {
+        let _guard = serial();
+        on_identity_changed(&serde_json::json!({ "hecate_user_id": "u-event" }));
+        assert_eq!(sent_user_id(None), vec!["u-event"]);
+        on_identity_changed(&serde_json::json!({ "identity": { "hecate_user_id": "u-nested" } }));
+        assert_eq!(sent_user_id(None), vec!["u-nested"]);
+    }
+
+    #[test]
+    fn forget_drops_the_identity() {
+        let _guard = serial();
+        set(Some("me".to_string()));
+        forget();
+        assert!(sent_user_id(Some("forged")).is_empty());
+    }
+
+    #[test]
+    fn user_id_shapes() {
+        let cases = [
+            (serde_json::json!({ "hecate_user_id": "a" }), Some("a")),
+            (serde_json::json!({ "identity": { "hecate_user_id": "b" } }), Some("b")),
+            (serde_json::json!({ "hecate_user_id": "" }), None),
+            (serde_json::json!({ "hecate_user_id": 7 }), None),
+            (serde_json::json!({ "identity": {} }), None),
+            (serde_json::json!("u"), None),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(user_id_in(&value).as_deref(), expected, "{}", value);
+        }
+    }
+}
+
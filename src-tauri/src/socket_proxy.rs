@@ -1,72 +1,91 @@
-use std::io::{BufRead, BufReader, Read, Write};
-use std::os::unix::net::UnixStream;
+use bytes::Bytes;
 use std::path::Path;
-use tauri::http::{Request, Response};
+use std::time::Duration;
+use tauri::http::header::{
+    ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_EXPOSE_HEADERS, CACHE_CONTROL, CONNECTION,
+    CONTENT_LENGTH, CONTENT_SECURITY_POLICY, CONTENT_TYPE, HOST, RANGE,
+};
+use tauri::http::{HeaderMap, HeaderName, HeaderValue, Method, Request, Response, StatusCode};
 
+use crate::config_watcher;
+use crate::correlation;
+use crate::daemon_client;
+use crate::daemon_profiles;
+use crate::daemon_watcher;
+use crate::har_recorder;
+use crate::offline_queue;
+use crate::plugin_frame;
+use crate::plugin_isolation;
+use crate::plugin_name::PluginName;
+use crate::proxy_error::ProxyError;
+use crate::proxy_limits;
+use crate::proxy_policy;
+use crate::response_cache;
+use crate::session_identity;
 use crate::traffic;
+use crate::transport;
+
+const HEALTH_TIMEOUT: Duration = Duration::from_secs(2);
+/// Longest the daemon may go silent, both before the response head and
+/// between body chunks.
+const PROXY_READ_TIMEOUT: Duration = Duration::from_secs(30);
+/// Most bytes asked for in one Range request. Media elements open with
+/// `bytes=0-` and keep asking for the next range as they play or seek.
+const MAX_RANGE_LEN: u64 = 2 * 1024 * 1024;
+/// `hecate://` hosts that always mean the main daemon, whatever the path.
+const DAEMON_HOSTS: &[&str] = &["daemon", "daemon.localhost"];
 
 /// Tauri command: check daemon health directly via Unix socket.
 /// Bypasses the custom URI scheme protocol entirely.
 #[tauri::command]
 pub fn check_daemon_health() -> Result<serde_json::Value, String> {
     let socket_path = resolve_socket_path();
-    if !Path::new(&socket_path).exists() {
+    if !transport::is_available(&socket_path) {
         return Err("socket_not_found".into());
     }
 
-    let mut stream = UnixStream::connect(&socket_path).map_err(|e| e.to_string())?;
-    stream
-        .set_read_timeout(Some(std::time::Duration::from_secs(2)))
-        .map_err(|e| e.to_string())?;
-    stream
-        .set_write_timeout(Some(std::time::Duration::from_secs(2)))
+    let mut request = daemon_client::request(Method::GET, &socket_path, "/health")
+        .body(daemon_client::empty())
         .map_err(|e| e.to_string())?;
+    let ids = correlation::Ids::generate();
+    ids.apply(request.headers_mut());
+    traffic::record_tx(daemon_client::head_len(&request) as u64);
 
-    let req = "GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
-    stream.write_all(req.as_bytes()).map_err(|e| e.to_string())?;
-
-    let mut reader = BufReader::new(stream);
-
-    // Status line
-    let mut status_line = String::new();
-    reader.read_line(&mut status_line).map_err(|e| e.to_string())?;
-    let status = parse_status_code(&status_line).map_err(|e| e.to_string())?;
-    if status != 200 {
-        return Err(format!("daemon returned {}", status));
-    }
+    daemon_client::block_on(async {
+        let response = tokio::time::timeout(HEALTH_TIMEOUT, daemon_client::send(request))
+            .await
+            .map_err(|_| "health check timed out".to_string())?
+            .map_err(|e| e.to_string())?;
 
-    // Headers
-    let mut content_length: Option<usize> = None;
-    loop {
-        let mut line = String::new();
-        reader.read_line(&mut line).map_err(|e| e.to_string())?;
-        if line.trim().is_empty() {
-            break;
-        }
-        if let Some((key, value)) = line.split_once(':') {
-            if key.trim().eq_ignore_ascii_case("content-length") {
-                content_length = value.trim().parse().ok();
-            }
+        let status = response.status().as_u16();
+        if status != 200 {
+            return Err(format!("daemon returned {}", status));
         }
-    }
 
-    // Body
-    let body = if let Some(len) = content_length {
-        let mut buf = vec![0u8; len];
-        reader.read_exact(&mut buf).map_err(|e| e.to_string())?;
-        buf
-    } else {
-        let mut buf = Vec::new();
-        let _ = reader.read_to_end(&mut buf);
-        buf
-    };
+        let buf = daemon_client::read_to_end(&mut response.into_body(), HEALTH_TIMEOUT)
+            .await
+            .map_err(|e| e.to_string())?;
+        traffic::record_rx(buf.len() as u64);
 
-    serde_json::from_slice(&body).map_err(|e| e.to_string())
+        serde_json::from_slice(&buf).map_err(|e| e.to_string())
+    })
+    .map_err(|e: String| format!("{} (request {})", e, ids.request_id))
 }
 
-/// Resolve the daemon socket path for the main hecate-daemon.
-/// Priority: HECATE_SOCKET_PATH env > /run/hecate/ > $HOME/.hecate/hecate-daemon/sockets/
+/// Resolve the endpoint of the main hecate-daemon: the active connection
+/// profile's socket (or `tcp://` URL), or the default resolution below.
 pub fn resolve_socket_path() -> String {
+    daemon_profiles::active_endpoint().unwrap_or_else(default_socket_path)
+}
+
+/// Socket of the built-in `default` profile.
+/// Priority: in-process hecate tree (mock daemon) > HECATE_SOCKET_PATH env >
+/// /run/hecate/ > $HOME/.hecate/hecate-daemon/sockets/
+pub fn default_socket_path() -> String {
+    if let Some(home) = config_watcher::hecate_home_override() {
+        let socket = home.join("hecate-daemon").join("sockets").join("api.sock");
+        return socket.to_string_lossy().to_string();
+    }
     if let Ok(p) = std::env::var("HECATE_SOCKET_PATH") {
         if !p.is_empty() && Path::new(&p).exists() {
             return p;
@@ -77,8 +96,8 @@ pub fn resolve_socket_path() -> String {
         return system.to_string();
     }
     // Local dev default: $HOME/.hecate/hecate-daemon/sockets/ (namespaced)
-    if let Ok(home) = std::env::var("HOME") {
-        let home_socket = Path::new(&home).join(".hecate").join("hecate-daemon").join("sockets").join("api.sock");
+    if let Some(home) = config_watcher::hecate_home() {
+        let home_socket = home.join("hecate-daemon").join("sockets").join("api.sock");
         return home_socket.to_string_lossy().to_string();
     }
     "/run/hecate/api.sock".to_string()
@@ -86,168 +105,610 @@ pub fn resolve_socket_path() -> String {
 
 /// Resolve socket path for a plugin daemon by name.
 /// Path: $HOME/.hecate/hecate-app-{name}d/sockets/api.sock
-pub fn resolve_plugin_socket_path(plugin_name: &str) -> String {
-    if let Ok(home) = std::env::var("HOME") {
-        let path = Path::new(&home)
-            .join(".hecate")
-            .join(format!("hecate-app-{}d", plugin_name))
+pub fn resolve_plugin_socket_path(plugin_name: &PluginName) -> String {
+    if let Some(home) = config_watcher::hecate_home() {
+        let path = home
+            .join(plugin_name.dir_name())
             .join("sockets")
             .join("api.sock");
         return path.to_string_lossy().to_string();
     }
-    format!("/run/hecate-app-{}d/api.sock", plugin_name)
+    format!("/run/{}/api.sock", plugin_name.dir_name())
 }
 
-/// Route a request path to the correct socket.
-/// /plugin/{name}/* -> hecate-app-{name}d socket (path rewritten to /*)
-/// Everything else  -> hecate-daemon socket (path unchanged)
-fn resolve_socket_for_path(path: &str) -> (String, String) {
-    if let Some(rest) = path.strip_prefix("/plugin/") {
-        if let Some(slash_pos) = rest.find('/') {
-            let plugin_name = &rest[..slash_pos];
-            let rewritten_path = &rest[slash_pos..];
-            return (resolve_plugin_socket_path(plugin_name), rewritten_path.to_string());
+/// Which daemon a `hecate://` request is addressed to.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Target {
+    /// The main hecate-daemon.
+    Daemon,
+    /// A plugin daemon, by name.
+    Plugin(PluginName),
+}
+
+impl std::fmt::Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Target::Daemon => write!(f, "daemon"),
+            Target::Plugin(name) => write!(f, "plugin {}", name),
         }
-        // /plugin/trader with no trailing path -> /
-        let plugin_name = rest;
-        return (resolve_plugin_socket_path(plugin_name), "/".to_string());
     }
-    (resolve_socket_path(), path.to_string())
 }
 
-pub fn proxy_request(
-    request: &Request<Vec<u8>>,
-) -> Result<Response<Vec<u8>>, Box<dyn std::error::Error>> {
-    let uri = request.uri();
+/// Where a proxied request goes: the target, its socket, and the path as the
+/// target daemon sees it.
+pub struct Route {
+    pub target: Target,
+    pub socket_path: String,
+    pub path: String,
+}
+
+/// Route a request to the correct socket, by host first and then by path.
+/// Host {name}.plugin[.localhost] -> hecate-app-{name}d socket (path unchanged)
+/// Host daemon[.localhost]        -> hecate-daemon socket (path unchanged)
+/// Any other host (`localhost`):
+///   /plugin/{name}/* -> hecate-app-{name}d socket (path rewritten to /*)
+///   Everything else  -> hecate-daemon socket (path unchanged)
+///
+/// A plugin UI loaded from its own host gets a distinct origin, and its
+/// root-relative URLs reach its own daemon without any rewriting.
+pub fn resolve_socket_for_path(host: Option<&str>, path: &str) -> Result<Route, ProxyError> {
+    if let Some(host) = host {
+        if let Some(plugin_name) = plugin_isolation::plugin_from_host(host) {
+            return plugin_route(&plugin_name, path);
+        }
+        if DAEMON_HOSTS.iter().any(|h| host.eq_ignore_ascii_case(h)) {
+            return Ok(daemon_route(path));
+        }
+    }
+    if let Some(rest) = path.strip_prefix("/plugin/") {
+        let (plugin_name, rewritten_path) = match rest.find('/') {
+            Some(slash_pos) => (&rest[..slash_pos], &rest[slash_pos..]),
+            // /plugin/trader with no trailing path -> /
+            None => (rest, "/"),
+        };
+        return plugin_route(plugin_name, rewritten_path);
+    }
+    Ok(daemon_route(path))
+}
+
+fn plugin_route(plugin_name: &str, path: &str) -> Result<Route, ProxyError> {
+    let plugin_name = PluginName::parse(plugin_name).map_err(ProxyError::InvalidPluginName)?;
+    let socket_path = resolve_plugin_socket_path(&plugin_name);
+    if !plugin_installed(&socket_path) {
+        return Err(ProxyError::UnknownPlugin(plugin_name.to_string()));
+    }
+    Ok(Route {
+        target: Target::Plugin(plugin_name),
+        socket_path,
+        path: path.to_string(),
+    })
+}
+
+fn daemon_route(path: &str) -> Route {
+    Route {
+        target: Target::Daemon,
+        socket_path: resolve_socket_path(),
+        path: path.to_string(),
+    }
+}
+
+/// A plugin counts as installed when its daemon directory exists, even if
+/// the daemon itself (and so its socket) is currently down.
+fn plugin_installed(socket_path: &str) -> bool {
+    let socket = Path::new(socket_path);
+    let daemon_dir = if socket.parent().and_then(|p| p.file_name()) == Some("sockets".as_ref()) {
+        socket.parent().and_then(Path::parent)
+    } else {
+        socket.parent()
+    };
+    daemon_dir.map(Path::is_dir).unwrap_or(false)
+}
+
+/// Proxy a `hecate://` request to the daemon (or plugin) socket.
+///
+/// Returns as soon as the status line and headers have been read. The body is
+/// left on the socket and handed back as a [`ProxyBody`] for the caller to
+/// pull chunk by chunk. The `hecate://` handler still collects it whole
+/// before answering, since Tauri's responder only takes a complete body, so
+/// responses are not streamed to the webview. Request bodies are not
+/// streamed either: the webview hands over the whole upload at once, so it
+/// is in memory in full (up to `max_body_bytes`) before it is sent on.
+///
+/// `Range` and `If-Range` are forwarded (single ranges capped at
+/// [`MAX_RANGE_LEN`]) and a `206 Partial Content` comes back with its
+/// `Content-Range`, so `<audio>`/`<video>` can seek in plugin media.
+///
+/// The request keeps the `X-Request-Id`/`traceparent` the webview set, or
+/// gets generated ones (see `correlation`); both are echoed on the response.
+/// `x-hecate-user-id` is always the shell's own (see `session_identity`).
+pub async fn proxy_request(request: Request<Vec<u8>>) -> Result<Response<ProxyBody>, ProxyError> {
+    let (mut request, body) = request.into_parts();
+    let ids = correlation::ensure(&mut request.headers);
+    session_identity::apply(&mut request.headers);
+    let mut response = forward(request, Bytes::from(body), &ids).await?;
+    ids.apply(response.headers_mut());
+    Ok(response)
+}
+
+async fn forward(
+    request: tauri::http::request::Parts,
+    body: Bytes,
+    ids: &correlation::Ids,
+) -> Result<Response<ProxyBody>, ProxyError> {
+    let uri = &request.uri;
     let path = uri.path();
     let query = uri.query().unwrap_or("");
-    let method = request.method().as_str();
 
-    let (socket_path, rewritten_path) = resolve_socket_for_path(path);
-    let mut stream = UnixStream::connect(&socket_path)?;
-    stream.set_read_timeout(Some(std::time::Duration::from_secs(30)))?;
+    if let Some(asset) = plugin_frame::asset(uri.host(), &request.method, path) {
+        return frame_response(asset);
+    }
+    let route = resolve_socket_for_path(uri.host(), path)?;
+    if !proxy_policy::check(&route.target, request.method.as_str(), &route.path) {
+        return Err(ProxyError::Forbidden(format!("{} {}", request.method, path)));
+    }
+    let caller = plugin_isolation::caller_of(&request.headers, uri.host());
+    if !plugin_isolation::permits(&caller, &route.target, &request.method, &route.path).await {
+        return Err(ProxyError::NotGranted(format!("{} {}", request.method, path)));
+    }
+    if har_recorder::is_replaying() {
+        let url = uri.to_string();
+        return match har_recorder::replay(&request.method, &url) {
+            Some((status, headers, body)) => client_response(status, &headers, ProxyBody::buffered(body)),
+            None => Err(ProxyError::ReplayMiss(format!("{} {}", request.method, url))),
+        };
+    }
+    let limit = proxy_policy::max_body_bytes();
+    if body.len() as u64 > limit {
+        return Err(ProxyError::PayloadTooLarge(limit));
+    }
+    let socket_path = route.socket_path;
 
     // Build the request path (using rewritten path for plugin routing)
     let full_path = if query.is_empty() {
-        rewritten_path
+        route.path.clone()
     } else {
-        format!("{}?{}", rewritten_path, query)
+        format!("{}?{}", route.path, query)
     };
 
-    let body = request.body();
+    let cache_key = response_cache::key(&route.target, &request.method, &full_path, &request.headers);
+    let cached = cache_key
+        .as_deref()
+        .and_then(|key| response_cache::lookup(key, &request.headers));
+
+    let socket_exists = transport::is_available(&socket_path);
+    let daemon_down = route.target == Target::Daemon && !daemon_watcher::is_healthy();
+    if !socket_exists || daemon_down {
+        if let Some(cached) = cached {
+            eprintln!("[socket_proxy] {} serving stale {} (target unavailable)", ids.request_id, full_path);
+            return stale_response(cached);
+        }
+    }
+    let queueable = route.target == Target::Daemon
+        && !request.method.is_safe()
+        && request.headers.contains_key(offline_queue::QUEUE_HEADER);
+    if queueable && (!socket_exists || daemon_down || offline_queue::is_pending()) {
+        if let Some(response) = queue_offline(&request, &body, ids) {
+            return response;
+        }
+    }
+    if !socket_exists {
+        return Err(ProxyError::SocketMissing(socket_path));
+    }
 
-    // Build HTTP/1.1 request
-    let mut http_req = format!(
-        "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n",
-        method, full_path
-    );
+    let permit = proxy_limits::acquire(&route.target).await?;
+    let body_len = body.len();
+    let mut upstream = daemon_client::request(request.method.clone(), &socket_path, &full_path)
+        .body(daemon_client::full(body.clone()))?;
+    let headers = upstream.headers_mut();
 
-    if !body.is_empty() {
-        http_req += &format!(
-            "Content-Type: application/json\r\nContent-Length: {}\r\n",
-            body.len()
-        );
+    // Forward every end-to-end header from the webview
+    for (name, value) in end_to_end_headers(&request.headers) {
+        if name == HOST || name == CONTENT_LENGTH || name == offline_queue::QUEUE_HEADER {
+            continue;
+        }
+        headers.append(name.clone(), value.clone());
+    }
+    // Keep the JSON default for callers that never set a Content-Type
+    if body_len > 0 && !headers.contains_key(CONTENT_TYPE) {
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
     }
+    if request.method == Method::GET {
+        bound_range(headers);
+    }
+    let revalidating = cached.as_ref().is_some_and(|c| c.add_validators(headers));
+
+    traffic::record_tx((daemon_client::head_len(&upstream) + body_len) as u64);
+    let capture = har_recorder::begin(upstream.method(), &uri.to_string(), upstream.headers(), &body);
 
-    // Forward select headers
-    for name in &["accept", "x-hecate-user-id"] {
-        if let Some(val) = request.headers().get(*name) {
-            if let Ok(v) = val.to_str() {
-                http_req += &format!("{}: {}\r\n", name, v);
+    let sent = tokio::time::timeout(PROXY_READ_TIMEOUT, daemon_client::send(upstream))
+        .await
+        .map_err(|_| ProxyError::Timeout)
+        .and_then(|r| r.map_err(|e| ProxyError::from_client(e, &socket_path)));
+    let upstream_response = match sent {
+        Ok(r) => r,
+        Err(e) => {
+            if let Some(c) = &capture {
+                c.fail(&e.to_string());
+            }
+            if let (Some(cached), true) = (cached, e.status() >= 503) {
+                eprintln!("[socket_proxy] {} serving stale {} ({})", ids.request_id, full_path, e);
+                return stale_response(cached);
+            }
+            // Refused before the daemon saw it: safe to queue
+            if queueable && e.retry_after().is_some() {
+                if let Some(response) = queue_offline(&request, &body, ids) {
+                    return response;
+                }
             }
+            return Err(e);
         }
+    };
+    let (parts, incoming) = upstream_response.into_parts();
+    if let Some(c) = &capture {
+        c.response(parts.status, &parts.headers);
     }
 
-    http_req += "\r\n";
+    if let (Some(cached), true) = (cached, revalidating && parts.status == StatusCode::NOT_MODIFIED) {
+        let cached = response_cache::refresh(cached, &parts.headers);
+        return client_response(cached.status(), &cached.headers(), ProxyBody::buffered(cached.into_body()));
+    }
+    if parts.status.is_success() && !request.method.is_safe() {
+        response_cache::invalidate_path(&route.target, &route.path);
+    }
 
-    stream.write_all(http_req.as_bytes())?;
-    if !body.is_empty() {
-        stream.write_all(body)?;
+    let mut body = ProxyBody::upstream(incoming);
+    body.capture = capture;
+    body.permit = Some(permit);
+    body.store = cache_key.and_then(|key| {
+        response_cache::store(key, &route.target, &route.path, &request.headers, parts.status, &parts.headers)
+    });
+    client_response(parts.status, &parts.headers, body)
+}
+
+/// Cap a single open-ended or oversized `Range: bytes=start-end` at
+/// [`MAX_RANGE_LEN`] bytes. The protocol handler has to hold a whole response
+/// in memory, so a `<video>` asking for `bytes=0-` of a large file gets the
+/// first piece as a 206 and fetches the rest range by range. `If-Range` and
+/// everything else about the request is left to the daemon.
+fn bound_range(headers: &mut HeaderMap) {
+    let Some(spec) = headers
+        .get(RANGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().strip_prefix("bytes="))
+    else {
+        return;
+    };
+    // Multiple ranges and suffix ranges (`-500`) go through untouched
+    let Some((start, end)) = spec.trim().split_once('-').filter(|_| !spec.contains(',')) else {
+        return;
+    };
+    let Ok(start) = start.trim().parse::<u64>() else {
+        return;
+    };
+    let last = start.saturating_add(MAX_RANGE_LEN - 1);
+    let bounded = match end.trim() {
+        "" => true,
+        end => end.parse::<u64>().is_ok_and(|end| end > last),
+    };
+    if bounded {
+        if let Ok(value) = HeaderValue::from_str(&format!("bytes={}-{}", start, last)) {
+            headers.insert(RANGE, value);
+        }
+    }
+}
+
+/// Persist an opted-in write for replay and answer `202 Accepted` with its
+/// queue id. `None` if it could not be queued; the request then goes ahead
+/// and fails as usual.
+fn queue_offline(
+    request: &tauri::http::request::Parts,
+    body: &[u8],
+    ids: &correlation::Ids,
+) -> Option<Result<Response<ProxyBody>, ProxyError>> {
+    let uri = request.uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+    match offline_queue::enqueue(&request.method, uri, &request.headers, body) {
+        Ok(id) => {
+            let mut headers = HeaderMap::new();
+            if let Ok(value) = HeaderValue::from_str(&id) {
+                headers.insert("x-hecate-queue-id", value);
+            }
+            Some(client_response(
+                StatusCode::ACCEPTED,
+                &headers,
+                ProxyBody::buffered(offline_queue::accepted_body(&id)),
+            ))
+        }
+        Err(e) => {
+            eprintln!("[socket_proxy] {} could not queue {} {}: {}", ids.request_id, request.method, uri, e);
+            None
+        }
+    }
+}
+
+/// Answer from the cache while the target is unreachable.
+fn stale_response(cached: response_cache::Cached) -> Result<Response<ProxyBody>, ProxyError> {
+    let (status, headers) = (cached.status(), cached.stale_headers());
+    client_response(status, &headers, ProxyBody::buffered(cached.into_body()))
+}
+
+/// Build the response handed to the webview from a daemon (or replayed)
+/// status and header set.
+fn client_response(
+    status: StatusCode,
+    upstream_headers: &HeaderMap,
+    body: ProxyBody,
+) -> Result<Response<ProxyBody>, ProxyError> {
+    let mut response = Response::builder().status(status).body(body)?;
+    let headers = response.headers_mut();
+    for (name, value) in end_to_end_headers(upstream_headers) {
+        headers.append(name.clone(), value.clone());
+    }
+    if !headers.contains_key(CONTENT_TYPE) {
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
     }
-    traffic::record_tx((http_req.len() + body.len()) as u64);
+    headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue::from_static("*"));
+    // Let the frontend read ETag, X-Total-Count, x-hecate-* and friends
+    headers.insert(ACCESS_CONTROL_EXPOSE_HEADERS, HeaderValue::from_static("*"));
 
-    // Parse HTTP response
-    let mut reader = BufReader::new(stream);
+    Ok(response)
+}
+
+/// A built-in plugin frame file, under the frame's own CSP.
+fn frame_response(asset: &plugin_frame::Asset) -> Result<Response<ProxyBody>, ProxyError> {
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(CONTENT_TYPE, asset.content_type)
+        .header(CONTENT_SECURITY_POLICY, plugin_frame::FRAME_CSP)
+        .header(CACHE_CONTROL, "no-store")
+        .body(ProxyBody::buffered(asset.body))?)
+}
 
-    // Read status line
-    let mut status_line = String::new();
-    reader.read_line(&mut status_line)?;
-    let status_code = parse_status_code(&status_line)?;
+/// Headers that only apply to a single connection and must never be
+/// forwarded by a proxy (RFC 9110 §7.6.1), plus Proxy-* auth headers.
+const HOP_BY_HOP: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
 
-    // Read headers
-    let mut content_type = String::from("application/json");
-    let mut content_length: Option<usize> = None;
-    let mut is_chunked = false;
+/// Iterate the end-to-end headers of `headers`: everything except the
+/// hop-by-hop set and any header named in a `Connection` header.
+fn end_to_end_headers(headers: &HeaderMap) -> impl Iterator<Item = (&HeaderName, &HeaderValue)> {
+    let connection_tokens: Vec<String> = headers
+        .get_all(CONNECTION)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|t| t.trim().to_ascii_lowercase())
+        .filter(|t| !t.is_empty())
+        .collect();
+
+    headers.iter().filter(move |(name, _)| {
+        let name = name.as_str();
+        !HOP_BY_HOP.contains(&name) && !connection_tokens.iter().any(|t| t == name)
+    })
+}
+
+/// Response body handed to the protocol handler: either still arriving from
+/// the daemon socket, or already in memory (replay).
+///
+/// Received bytes are counted towards the traffic counters as they arrive,
+/// copied to the HAR recorder when a capture is attached, and collected for
+/// the response cache when the response is cacheable.
+pub struct ProxyBody {
+    source: BodySource,
+    capture: Option<har_recorder::Capture>,
+    store: Option<response_cache::Pending>,
+    /// Concurrency slot, released once the body is drained or dropped.
+    permit: Option<proxy_limits::Permit>,
+}
+
+enum BodySource {
+    Upstream(hyper::body::Incoming),
+    Buffered(Option<Bytes>),
+}
+
+impl ProxyBody {
+    fn upstream(incoming: hyper::body::Incoming) -> Self {
+        ProxyBody {
+            source: BodySource::Upstream(incoming),
+            capture: None,
+            store: None,
+            permit: None,
+        }
+    }
+
+    fn buffered(data: impl Into<Bytes>) -> Self {
+        ProxyBody {
+            source: BodySource::Buffered(Some(data.into())),
+            capture: None,
+            store: None,
+            permit: None,
+        }
+    }
 
-    loop {
-        let mut line = String::new();
-        reader.read_line(&mut line)?;
-        let trimmed = line.trim();
-        if trimmed.is_empty() {
-            break;
+    /// Size of the body if the daemon announced one, for preallocation.
+    pub fn size_hint(&self) -> Option<usize> {
+        match &self.source {
+            BodySource::Upstream(incoming) => hyper::body::Body::size_hint(incoming)
+                .exact()
+                .map(|n| n as usize),
+            BodySource::Buffered(data) => Some(data.as_ref().map(Bytes::len).unwrap_or(0)),
         }
-        if let Some((key, value)) = trimmed.split_once(':') {
-            let key_lower = key.trim().to_lowercase();
-            let value_trimmed = value.trim();
-            match key_lower.as_str() {
-                "content-type" => content_type = value_trimmed.to_string(),
-                "content-length" => content_length = value_trimmed.parse().ok(),
-                "transfer-encoding" => is_chunked = value_trimmed.to_lowercase().contains("chunked"),
-                _ => {}
+    }
+
+    /// Next chunk of the body, or `None` once the daemon has sent it all.
+    pub async fn next_chunk(&mut self) -> Option<Result<Bytes, ProxyError>> {
+        let incoming = match &mut self.source {
+            BodySource::Upstream(incoming) => incoming,
+            BodySource::Buffered(data) => return data.take().map(Ok),
+        };
+        let chunk = match tokio::time::timeout(PROXY_READ_TIMEOUT, daemon_client::next_chunk(incoming)).await {
+            Ok(Some(chunk)) => chunk.map_err(ProxyError::from_body),
+            Ok(None) => {
+                // Complete body: only now is it safe to cache
+                if let Some(store) = self.store.take() {
+                    store.commit();
+                }
+                return None;
+            }
+            Err(_) => Err(ProxyError::Timeout),
+        };
+        match &chunk {
+            Ok(data) => {
+                traffic::record_rx(data.len() as u64);
+                if let Some(c) = &self.capture {
+                    c.body(data);
+                }
+                if let Some(store) = &mut self.store {
+                    store.push(data);
+                }
+            }
+            Err(e) => {
+                if let Some(c) = &self.capture {
+                    c.fail(&e.to_string());
+                }
+                self.store = None;
             }
         }
+        Some(chunk)
     }
 
-    // Read body
-    let response_body = if let Some(len) = content_length {
-        let mut buf = vec![0u8; len];
-        reader.read_exact(&mut buf)?;
-        buf
-    } else if is_chunked {
-        read_chunked_body(&mut reader)?
-    } else {
-        // Read until EOF
-        let mut buf = Vec::new();
-        let _ = reader.read_to_end(&mut buf);
-        buf
-    };
+    /// Drain the body chunk by chunk, handing each one to `sink`.
+    pub async fn for_each_chunk(mut self, mut sink: impl FnMut(&[u8])) -> Result<(), ProxyError> {
+        while let Some(chunk) = self.next_chunk().await {
+            sink(&chunk?);
+        }
+        Ok(())
+    }
+}
 
-    traffic::record_rx(response_body.len() as u64);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
 
-    Ok(Response::builder()
-        .status(status_code)
-        .header("Content-Type", &content_type)
-        .header("Access-Control-Allow-Origin", "*")
-        .body(response_body)?)
-}
-
-fn parse_status_code(status_line: &str) -> Result<u16, Box<dyn std::error::Error>> {
-    // "HTTP/1.1 200 OK\r\n"
-    let parts: Vec<&str> = status_line.split_whitespace().collect();
-    if parts.len() < 2 {
-        return Err(format!("invalid status line: {}", status_line).into());
-    }
-    Ok(parts[1].parse()?)
-}
-
-fn read_chunked_body(reader: &mut BufReader<UnixStream>) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-    let mut body = Vec::new();
-    loop {
-        let mut size_line = String::new();
-        reader.read_line(&mut size_line)?;
-        let size = usize::from_str_radix(size_line.trim(), 16)?;
-        if size == 0 {
-            // Read trailing \r\n
-            let mut _trail = String::new();
-            let _ = reader.read_line(&mut _trail);
-            break;
-        }
-        let mut chunk = vec![0u8; size];
-        reader.read_exact(&mut chunk)?;
-        body.extend_from_slice(&chunk);
-        // Read trailing \r\n after chunk
-        let mut _trail = String::new();
-        reader.read_line(&mut _trail)?;
-    }
-    Ok(body)
+    fn plugin(name: &str) -> Target {
+        Target::Plugin(PluginName::parse(name).unwrap())
+    }
+
+    /// Where a request goes, as (target, path), or the error status.
+    fn route(host: Option<&str>, path: &str) -> Result<(Target, String), u16> {
+        resolve_socket_for_path(host, path)
+            .map(|r| (r.target, r.path))
+            .map_err(|e| e.status())
+    }
+
+    fn install(name: &str) {
+        let dir = config_watcher::test_home().join(PluginName::parse(name).unwrap().dir_name());
+        std::fs::create_dir_all(dir.join("sockets")).unwrap();
+    }
+
+    #[test]
+    fn routes_by_host_then_path() {
+        install("demo");
+        let daemon = |path: &str| Ok((Target::Daemon, path.to_string()));
+        let demo = |path: &str| Ok((plugin("demo"), path.to_string()));
+        let cases = [
+            (None, "/api/health", daemon("/api/health")),
+            (Some("localhost"), "/api/health", daemon("/api/health")),
+            (Some("localhost"), "/plugin/demo/api/items?x=1", demo("/api/items?x=1")),
+            (Some("localhost"), "/plugin/demo", demo("/")),
+            (Some("localhost"), "/plugin/demo/", demo("/")),
+            // A plugin host keeps its paths as they are, /plugin/ or not
+            (Some("demo.plugin"), "/api/items", demo("/api/items")),
+            (Some("demo.plugin.localhost"), "/plugin/other/x", demo("/plugin/other/x")),
+            (Some("DEMO.plugin"), "/", demo("/")),
+            // So does the daemon host
+            (Some("daemon"), "/plugin/demo/x", daemon("/plugin/demo/x")),
+            (Some("Daemon.localhost"), "/api/x", daemon("/api/x")),
+            (Some("localhost"), "/plugin/-bad/x", Err(400)),
+            (Some("localhost"), "/plugin//x", Err(400)),
+            (Some("localhost"), "/plugin/missing/x", Err(404)),
+            (Some("missing.plugin"), "/x", Err(404)),
+        ];
+        for (host, path, expected) in cases {
+            assert_eq!(route(host, path), expected, "{:?} {}", host, path);
+        }
+        let socket = resolve_socket_for_path(Some("demo.plugin"), "/").unwrap().socket_path;
+        assert_eq!(socket, resolve_plugin_socket_path(&PluginName::parse("demo").unwrap()));
+    }
+
+    #[test]
+    fn installed_means_the_daemon_dir_exists() {
+        install("present");
+        let home = config_watcher::test_home();
+        let present = home.join("hecate-app-presentd");
+        let cases = [
+            (present.join("sockets").join("api.sock"), true),
+            // A socket outside `sockets/` counts its own directory
+            (present.join("api.sock"), true),
+            (home.join("hecate-app-absentd").join("sockets").join("api.sock"), false),
+            (PathBuf::from("api.sock"), false),
+        ];
+        for (socket, expected) in cases {
+            assert_eq!(plugin_installed(&socket.to_string_lossy()), expected, "{}", socket.display());
+        }
+    }
+
+    #[test]
+    fn hop_by_hop_headers_are_dropped() {
+        let mut headers = HeaderMap::new();
+        for (name, value) in [
+            ("connection", "keep-alive, X-Private"),
+            ("connection", "x-other"),
+            ("keep-alive", "timeout=5"),
+            ("transfer-encoding", "chunked"),
+            ("upgrade", "websocket"),
+            ("te", "trailers"),
+            ("trailer", "expires"),
+            ("proxy-authorization", "secret"),
+            ("x-private", "1"),
+            ("x-other", "2"),
+            ("content-type", "application/json"),
+            ("etag", "\"v1\""),
+            ("x-request-id", "r-1"),
+        ] {
+            headers.append(name, HeaderValue::from_static(value));
+        }
+        let kept: Vec<&str> = end_to_end_headers(&headers).map(|(name, _)| name.as_str()).collect();
+        assert_eq!(kept, vec!["content-type", "etag", "x-request-id"]);
+    }
+
+    #[test]
+    fn ranges_are_bounded() {
+        let max_end = MAX_RANGE_LEN - 1;
+        let cases = [
+            // Open-ended ranges get an end
+            ("bytes=0-", format!("bytes=0-{}", max_end)),
+            ("bytes=100-", format!("bytes=100-{}", 100 + max_end)),
+            // Too long: cut to the limit
+            ("bytes=0-99999999", format!("bytes=0-{}", max_end)),
+            (&format!("bytes=0-{}", max_end + 1), format!("bytes=0-{}", max_end)),
+            // Within the limit, suffix, multiple or malformed: untouched
+            (&format!("bytes=0-{}", max_end), format!("bytes=0-{}", max_end)),
+            ("bytes=0-99", "bytes=0-99".to_string()),
+            ("bytes=-500", "bytes=-500".to_string()),
+            ("bytes=0-1,5-", "bytes=0-1,5-".to_string()),
+            ("bytes=x-", "bytes=x-".to_string()),
+            ("items=0-", "items=0-".to_string()),
+        ];
+        for (range, expected) in cases {
+            let mut headers = HeaderMap::new();
+            headers.insert(RANGE, HeaderValue::from_str(range).unwrap());
+            bound_range(&mut headers);
+            assert_eq!(headers[RANGE], expected.as_str(), "{}", range);
+        }
+
+        let mut none = HeaderMap::new();
+        bound_range(&mut none);
+        assert!(none.get(RANGE).is_none());
+    }
 }
+
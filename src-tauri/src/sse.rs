@@ -0,0 +1,351 @@
+//! Server-sent events decoder, shared by the daemon and plugin streams.
+//!
+//! Follows the WHATWG event-stream interpretation rules: a leading BOM is
+//! dropped, lines end in CR, LF or CRLF (also when split across chunks),
+//! `data:` lines are joined with newlines, `event:` names the type (default
+//! `message`), `id:` sets the event id that carries over between events,
+//! and `retry:` sets the reconnection time. The id only becomes the last
+//! event id once its event is dispatched. Comment lines and
+//! unknown fields are ignored, and an event still open when the stream ends
+//! is discarded.
+
+use std::time::Duration;
+
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+const DEFAULT_EVENT_TYPE: &str = "message";
+
+/// One dispatched event.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Event {
+    /// The `event:` field, or `message`.
+    pub event_type: String,
+    /// Every `data:` line of the event, joined with `\n`.
+    pub data: String,
+    /// The last event id at the time of dispatch, if any.
+    pub id: Option<String>,
+}
+
+/// Incremental decoder: feed it body chunks as they arrive.
+#[derive(Default)]
+pub struct Decoder {
+    buf: Vec<u8>,
+    /// Whether the start of the stream (and any BOM) has been dealt with.
+    started: bool,
+    /// The previous chunk ended in CR; an LF opening the next one is part
+    /// of the same line ending.
+    after_cr: bool,
+    event_type: String,
+    data: String,
+    /// The id set by the latest `id:` line, dispatched or not.
+    id_buffer: String,
+    /// The id as of the last dispatch.
+    last_event_id: String,
+    retry: Option<Duration>,
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A decoder for a reconnection: events without an `id:` of their own
+    /// carry the id the previous connection left off at.
+    pub fn resuming(last_event_id: Option<&str>) -> Self {
+        let id = last_event_id.unwrap_or_default().to_string();
+        Decoder {
+            id_buffer: id.clone(),
+            last_event_id: id,
+            ..Self::default()
+        }
+    }
+
+    /// The id to send as `Last-Event-ID` when reconnecting: that of the last
+    /// dispatched event, never one still being received.
+    pub fn last_event_id(&self) -> Option<&str> {
+        Some(self.last_event_id.as_str()).filter(|id| !id.is_empty())
+    }
+
+    /// The reconnection time the server last asked for with `retry:`.
+    pub fn retry(&self) -> Option<Duration> {
+        self.retry
+    }
+
+    /// Decode `chunk`, returning the events it completes.
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<Event> {
+        self.buf.extend_from_slice(chunk);
+        if !self.started {
+            // Wait until a BOM could be told apart from a prefix of one
+            if self.buf.len() < BOM.len() && BOM.starts_with(&self.buf) {
+                return Vec::new();
+            }
+            if self.buf.starts_with(BOM) {
+                self.buf.drain(..BOM.len());
+            }
+            self.started = true;
+        }
+
+        let mut events = Vec::new();
+        let mut start = 0;
+        let mut i = 0;
+        while i < self.buf.len() {
+            match self.buf[i] {
+                b'\n' if self.after_cr && i == start => {
+                    // Second half of a CRLF split across chunks
+                    start = i + 1;
+                }
+                b'\r' | b'\n' => {
+                    let line = String::from_utf8_lossy(&self.buf[start..i]).into_owned();
+                    if self.buf[i] == b'\r' && self.buf.get(i + 1) == Some(&b'\n') {
+                        i += 1;
+                    }
+                    start = i + 1;
+                    events.extend(self.line(&line));
+                }
+                _ => {}
+            }
+            self.after_cr = self.buf[i] == b'\r';
+            i += 1;
+        }
+        self.buf.drain(..start);
+        events
+    }
+
+    fn line(&mut self, line: &str) -> Option<Event> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        if line.starts_with(':') {
+            return None;
+        }
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "event" => self.event_type = value.to_string(),
+            "data" => {
+                self.data.push_str(value);
+                self.data.push('\n');
+            }
+            "id" if !value.contains('\0') => self.id_buffer = value.to_string(),
+            "retry" if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) => {
+                if let Ok(ms) = value.parse() {
+                    self.retry = Some(Duration::from_millis(ms));
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<Event> {
+        self.last_event_id.clone_from(&self.id_buffer);
+        let event_type = std::mem::take(&mut self.event_type);
+        let mut data = std::mem::take(&mut self.data);
+        if data.is_empty() {
+            return None;
+        }
+        data.pop();
+        Some(Event {
+            event_type: if event_type.is_empty() {
+                DEFAULT_EVENT_TYPE.to_string()
+            } else {
+                event_type
+            },
+            data,
+            id: self.last_event_id().map(str::to_string),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(event_type: &str, data: &str, id: Option<&str>) -> Event {
+        Event {
+            event_type: event_type.to_string(),
+            data: data.to_string(),
+            id: id.map(str::to_string),
+        }
+    }
+
+    fn decode(stream: &str) -> Vec<Event> {
+        Decoder::new().feed(stream.as_bytes())
+    }
+
+    /// Feed the stream one byte at a time.
+    fn decode_bytewise(stream: &[u8]) -> Vec<Event> {
+        let mut decoder = Decoder::new();
+        stream.iter().flat_map(|b| decoder.feed(&[*b])).collect()
+    }
+
+    #[test]
+    fn single_event_with_default_type() {
+        assert_eq!(decode("data: hello\n\n"), vec![event("message", "hello", None)]);
+    }
+
+    #[test]
+    fn named_event() {
+        assert_eq!(
+            decode("event: settings_changed\ndata: {\"a\":1}\n\n"),
+            vec![event("settings_changed", "{\"a\":1}", None)]
+        );
+    }
+
+    #[test]
+    fn multi_line_data_is_joined_with_newlines() {
+        assert_eq!(
+            decode("data: first\ndata: second\ndata:third\n\n"),
+            vec![event("message", "first\nsecond\nthird", None)]
+        );
+    }
+
+    #[test]
+    fn only_one_leading_space_is_stripped() {
+        assert_eq!(decode("data:  two\n\n"), vec![event("message", " two", None)]);
+    }
+
+    #[test]
+    fn field_without_colon_has_empty_value() {
+        assert_eq!(decode("data\n\n"), vec![event("message", "", None)]);
+        assert_eq!(decode("data\ndata\n\n"), vec![event("message", "\n", None)]);
+    }
+
+    #[test]
+    fn event_without_data_is_not_dispatched() {
+        assert_eq!(decode("event: ping\n\n"), vec![]);
+        // ...and its type does not leak into the next event
+        assert_eq!(decode("event: ping\n\ndata: x\n\n"), vec![event("message", "x", None)]);
+    }
+
+    #[test]
+    fn comments_and_unknown_fields_are_ignored() {
+        assert_eq!(
+            decode(": heartbeat\nfoo: bar\ndata: x\n: more\n\n"),
+            vec![event("message", "x", None)]
+        );
+    }
+
+    #[test]
+    fn unterminated_event_is_not_dispatched() {
+        assert_eq!(decode("data: complete\n\ndata: partial\n"), vec![event("message", "complete", None)]);
+    }
+
+    #[test]
+    fn line_endings() {
+        let expected = vec![event("a", "1\n2", None), event("message", "3", None)];
+        assert_eq!(decode("event: a\ndata: 1\ndata: 2\n\ndata: 3\n\n"), expected);
+        assert_eq!(decode("event: a\rdata: 1\rdata: 2\r\rdata: 3\r\r"), expected);
+        assert_eq!(decode("event: a\r\ndata: 1\r\ndata: 2\r\n\r\ndata: 3\r\n\r\n"), expected);
+        assert_eq!(decode("event: a\r\ndata: 1\rdata: 2\n\r\ndata: 3\n\r"), expected);
+    }
+
+    #[test]
+    fn crlf_split_across_chunks_is_one_line_ending() {
+        let mut decoder = Decoder::new();
+        assert_eq!(decoder.feed(b"data: x\r"), vec![]);
+        assert_eq!(decoder.feed(b"\n\r"), vec![event("message", "x", None)]);
+        assert_eq!(decoder.feed(b"\ndata: y\r\n\r\n"), vec![event("message", "y", None)]);
+    }
+
+    #[test]
+    fn bytewise_feeding_matches_whole() {
+        let stream = "\u{feff}event: a\r\ndata: é\r\nid: 7\r\n\r\ndata: b\rretry: 50\r\r";
+        assert_eq!(decode_bytewise(stream.as_bytes()), decode(stream));
+        assert_eq!(
+            decode(stream),
+            vec![event("a", "é", Some("7")), event("message", "b", Some("7"))]
+        );
+    }
+
+    #[test]
+    fn leading_bom_is_dropped_once() {
+        assert_eq!(decode("\u{feff}data: x\n\n"), vec![event("message", "x", None)]);
+        // A second BOM is part of the field name, which is then unknown
+        assert_eq!(decode("\u{feff}\u{feff}data: x\n\n"), vec![]);
+        // Split across chunks
+        let mut decoder = Decoder::new();
+        assert_eq!(decoder.feed(b"\xEF\xBB"), vec![]);
+        assert_eq!(decoder.feed(b"\xBFdata: x\n\n"), vec![event("message", "x", None)]);
+    }
+
+    #[test]
+    fn short_stream_that_is_not_a_bom() {
+        let mut decoder = Decoder::new();
+        assert_eq!(decoder.feed(b"d"), vec![]);
+        assert_eq!(decoder.feed(b"ata: x\n\n"), vec![event("message", "x", None)]);
+    }
+
+    #[test]
+    fn id_carries_over_until_changed() {
+        assert_eq!(
+            decode("id: 1\ndata: a\n\ndata: b\n\nid: 2\ndata: c\n\nid\ndata: d\n\n"),
+            vec![
+                event("message", "a", Some("1")),
+                event("message", "b", Some("1")),
+                event("message", "c", Some("2")),
+                event("message", "d", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn id_with_nul_is_ignored() {
+        let mut decoder = Decoder::new();
+        decoder.feed(b"id: 1\n\nid: a\0b\n\n");
+        assert_eq!(decoder.last_event_id(), Some("1"));
+    }
+
+    #[test]
+    fn id_without_data_still_updates_last_event_id() {
+        let mut decoder = Decoder::new();
+        assert_eq!(decoder.feed(b"id: 9\n\n"), vec![]);
+        assert_eq!(decoder.last_event_id(), Some("9"));
+    }
+
+    #[test]
+    fn id_of_unterminated_event_is_not_last_event_id() {
+        let mut decoder = Decoder::new();
+        assert_eq!(decoder.feed(b"id: 5\ndata: x\n"), vec![]);
+        // Stream ends here: the event was never dispatched
+        assert_eq!(decoder.last_event_id(), None);
+
+        let mut decoder = Decoder::resuming(Some("4"));
+        assert_eq!(decoder.feed(b"id: 5\ndata: x\n"), vec![]);
+        assert_eq!(decoder.last_event_id(), Some("4"));
+        assert_eq!(decoder.feed(b"\n"), vec![event("message", "x", Some("5"))]);
+        assert_eq!(decoder.last_event_id(), Some("5"));
+    }
+
+    #[test]
+    fn resuming_decoder_keeps_previous_id() {
+        let mut decoder = Decoder::resuming(Some("41"));
+        assert_eq!(decoder.last_event_id(), Some("41"));
+        assert_eq!(decoder.feed(b"data: x\n\n"), vec![event("message", "x", Some("41"))]);
+    }
+
+    #[test]
+    fn retry_must_be_all_digits() {
+        let mut decoder = Decoder::new();
+        decoder.feed(b"retry: 2500\n");
+        assert_eq!(decoder.retry(), Some(Duration::from_millis(2500)));
+        decoder.feed(b"retry: 10s\nretry: -1\nretry:\nretry: 1.5\n");
+        assert_eq!(decoder.retry(), Some(Duration::from_millis(2500)));
+        decoder.feed(b"retry:0\n");
+        assert_eq!(decoder.retry(), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn invalid_utf8_is_replaced() {
+        assert_eq!(
+            Decoder::new().feed(b"data: a\xFFb\n\n"),
+            vec![event("message", "a\u{fffd}b", None)]
+        );
+    }
+
+    #[test]
+    fn empty_event_type_falls_back_to_message() {
+        assert_eq!(decode("event:\ndata: x\n\n"), vec![event("message", "x", None)]);
+    }
+}
@@ -0,0 +1,299 @@
+//! Byte transports to a daemon: a local Unix socket, or a remote daemon
+//! over TCP with TLS.
+//!
+//! A daemon endpoint is a string: a filesystem path for a Unix socket, or
+//! `tcp://host:port` for a remote daemon. Remote connections are always TLS;
+//! the CA, optional client certificate and server name come from the
+//! connection profile (see `daemon_profiles`) and default to the public web
+//! roots and the URL's host.
+//!
+//! The proxy, health checks, SSE streams and WebSocket tunnels all connect
+//! through [`connect`], so they follow whatever the active profile points at.
+
+use hyper_util::client::legacy::connect::{Connected, Connection};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use rustls::{ClientConfig, RootCertStore};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpStream, UnixStream};
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::TlsConnector;
+
+const TCP_SCHEME: &str = "tcp://";
+
+/// TLS settings for a remote daemon, as written in a connection profile.
+/// Paths are PEM files.
+#[derive(Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct TlsOptions {
+    /// CA bundle the daemon's certificate must chain to (default: web roots).
+    #[serde(default)]
+    pub ca: Option<String>,
+    /// Client certificate chain, for daemons that require mutual TLS.
+    #[serde(default)]
+    pub cert: Option<String>,
+    /// Private key for `cert`.
+    #[serde(default)]
+    pub key: Option<String>,
+    /// Name to verify the daemon's certificate against (default: URL host).
+    #[serde(default)]
+    pub server_name: Option<String>,
+}
+
+/// TLS options for an endpoint, and the client config built from them.
+type TlsEntry = (TlsOptions, Option<Arc<ClientConfig>>);
+
+/// TLS settings per remote endpoint.
+static TLS: Mutex<Option<HashMap<String, TlsEntry>>> = Mutex::new(None);
+
+/// A connected daemon transport.
+pub enum Stream {
+    Unix(UnixStream),
+    Tls(Box<TlsStream<TcpStream>>),
+}
+
+/// Whether `endpoint` names a remote (`tcp://`) daemon.
+pub fn is_remote(endpoint: &str) -> bool {
+    endpoint.starts_with(TCP_SCHEME)
+}
+
+/// Whether it is worth trying to connect: the socket file exists, or the
+/// endpoint is remote (which can only be known by connecting).
+pub fn is_available(endpoint: &str) -> bool {
+    is_remote(endpoint) || Path::new(endpoint).exists()
+}
+
+/// Value for the `Host` header: `host:port` for a remote daemon, else
+/// `localhost`.
+pub fn host_header(endpoint: &str) -> &str {
+    endpoint.strip_prefix(TCP_SCHEME).unwrap_or("localhost")
+}
+
+/// Set the TLS options used for `endpoint`. Called when profiles load.
+/// Returns whether they changed; pooled connections to the endpoint were
+/// then made with the old ones.
+pub fn configure_tls(endpoint: &str, options: TlsOptions) -> bool {
+    let Ok(mut tls) = TLS.lock() else {
+        return false;
+    };
+    let tls = tls.get_or_insert_with(HashMap::new);
+    if tls.get(endpoint).map(|(o, _)| o) == Some(&options) {
+        return false;
+    }
+    tls.insert(endpoint.to_string(), (options, None));
+    true
+}
+
+/// Open a connection to `endpoint`.
+pub async fn connect(endpoint: &str) -> io::Result<Stream> {
+    let Some(addr) = endpoint.strip_prefix(TCP_SCHEME) else {
+        return Ok(Stream::Unix(UnixStream::connect(endpoint).await?));
+    };
+    let (config, server_name) = tls_config(endpoint)?;
+    let server_name = ServerName::try_from(server_name)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("bad TLS server name: {}", e)))?;
+    let tcp = TcpStream::connect(addr).await?;
+    tcp.set_nodelay(true).ok();
+    let tls = TlsConnector::from(config).connect(server_name, tcp).await?;
+    Ok(Stream::Tls(Box::new(tls)))
+}
+
+/// Client config and server name for a remote endpoint, built on first use.
+fn tls_config(endpoint: &str) -> io::Result<(Arc<ClientConfig>, String)> {
+    let mut tls = TLS.lock().map_err(|_| io::Error::other("TLS settings poisoned"))?;
+    let (options, config) = tls
+        .get_or_insert_with(HashMap::new)
+        .entry(endpoint.to_string())
+        .or_insert_with(|| (TlsOptions::default(), None));
+
+    let host = endpoint
+        .strip_prefix(TCP_SCHEME)
+        .and_then(|addr| addr.rsplit_once(':').map(|(host, _)| host))
+        .unwrap_or(endpoint)
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    let server_name = options.server_name.clone().unwrap_or_else(|| host.to_string());
+
+    if let Some(config) = config {
+        return Ok((config.clone(), server_name));
+    }
+    let built = Arc::new(
+        build_client_config(options)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("TLS setup for {}: {}", endpoint, e)))?,
+    );
+    *config = Some(built.clone());
+    Ok((built, server_name))
+}
+
+fn build_client_config(options: &TlsOptions) -> Result<ClientConfig, Box<dyn std::error::Error>> {
+    let mut roots = RootCertStore::empty();
+    match &options.ca {
+        Some(ca) => {
+            for cert in CertificateDer::pem_file_iter(ca)? {
+                roots.add(cert?)?;
+            }
+        }
+        None => roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+    }
+
+    let builder = ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()?
+        .with_root_certificates(roots);
+    let config = match (&options.cert, &options.key) {
+        (Some(cert), Some(key)) => {
+            let chain = CertificateDer::pem_file_iter(cert)?.collect::<Result<Vec<_>, _>>()?;
+            builder.with_client_auth_cert(chain, PrivateKeyDer::from_pem_file(key)?)?
+        }
+        (None, None) => builder.with_no_client_auth(),
+        _ => return Err("tls.cert and tls.key must be set together".into()),
+    };
+    Ok(config)
+}
+
+impl Connection for Stream {
+    fn connected(&self) -> Connected {
+        Connected::new()
+    }
+}
+
+impl AsyncRead for Stream {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Unix(s) => Pin::new(s).poll_read(cx, buf),
+            Stream::Tls(s) => Pin::new(s.as_mut()).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Stream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Stream::Unix(s) => Pin::new(s).poll_write(cx, buf),
+            Stream::Tls(s) => Pin::new(s.as_mut()).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Unix(s) => Pin::new(s).poll_flush(cx),
+            Stream::Tls(s) => Pin::new(s.as_mut()).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Unix(s) => Pin::new(s).poll_shutdown(cx),
+            Stream::Tls(s) => Pin::new(s.as_mut()).poll_shutdown(cx),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon_client;
+    use hyper::Method;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio_rustls::TlsAcceptor;
+
+    const CA: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../fixtures/tls/ca.pem");
+    const SERVER_CERT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../fixtures/tls/server.pem");
+    const SERVER_KEY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../fixtures/tls/server.key");
+
+    /// A TLS server on a local port answering every request with `secure`.
+    async fn tls_server() -> String {
+        let chain = CertificateDer::pem_file_iter(SERVER_CERT)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let key = PrivateKeyDer::from_pem_file(SERVER_KEY).unwrap();
+        let config = rustls::ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(chain, key)
+            .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("{}{}", TCP_SCHEME, listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((tcp, _)) = listener.accept().await {
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    // Handshakes the client rejects end here
+                    let Ok(mut tls) = acceptor.accept(tcp).await else {
+                        return;
+                    };
+                    let mut buf = vec![0u8; 4096];
+                    let _ = tls.read(&mut buf).await;
+                    let _ = tls
+                        .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 6\r\n\r\nsecure")
+                        .await;
+                    let _ = tls.flush().await;
+                });
+            }
+        });
+        endpoint
+    }
+
+    #[tokio::test]
+    async fn request_over_tls_with_profile_ca() {
+        let endpoint = tls_server().await;
+        configure_tls(
+            &endpoint,
+            TlsOptions {
+                ca: Some(CA.to_string()),
+                server_name: Some("daemon.test".to_string()),
+                ..TlsOptions::default()
+            },
+        );
+        let request = daemon_client::request(Method::GET, &endpoint, "/health")
+            .body(daemon_client::empty())
+            .unwrap();
+        let response = daemon_client::send(request).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let body = daemon_client::read_to_end(&mut response.into_body(), std::time::Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(body, b"secure");
+    }
+
+    #[tokio::test]
+    async fn certificate_outside_the_trusted_roots_is_rejected() {
+        let endpoint = tls_server().await;
+        // Web roots only: the test CA is not among them
+        configure_tls(&endpoint, TlsOptions::default());
+        let err = connect(&endpoint).await.err().expect("untrusted certificate accepted");
+        assert!(err.to_string().to_lowercase().contains("certificate"), "{}", err);
+
+        // Right CA, wrong name
+        configure_tls(
+            &endpoint,
+            TlsOptions {
+                ca: Some(CA.to_string()),
+                server_name: Some("other.test".to_string()),
+                ..TlsOptions::default()
+            },
+        );
+        assert!(connect(&endpoint).await.is_err());
+    }
+
+    #[test]
+    fn configure_tls_reports_changes() {
+        let endpoint = "tcp://changes.test:4443";
+        let options = TlsOptions {
+            server_name: Some("a.test".to_string()),
+            ..TlsOptions::default()
+        };
+        assert!(configure_tls(endpoint, options.clone()));
+        assert!(!configure_tls(endpoint, options));
+        assert!(configure_tls(endpoint, TlsOptions::default()));
+    }
+}
@@ -0,0 +1,271 @@
+//! WebSocket tunnels from the webview to daemon and plugin sockets.
+//!
+//! `ws_open` performs the HTTP Upgrade over the same socket the
+//! `hecate://` proxy would use for that path (`/api/...` for the daemon,
+//! `/plugin/{name}/...` for a plugin, or a full `hecate://{name}.plugin/...`
+//! URL to route by host) and returns a connection id. Frames
+//! from the daemon are delivered on the `Channel` passed to `ws_open`;
+//! `ws_send` and `ws_close` address the connection by id. Nothing listens
+//! on a TCP port for the webview.
+//!
+//! Plugin frames have no IPC of their own; the shell opens sockets for them
+//! through its frame bridge (`src/lib/plugin-bridge.ts`), passing the
+//! frame's plugin as `plugin`.
+//!
+//! The upgrade is checked like any proxied `GET`: access policy, plugin
+//! isolation (as `plugin` when given, else by the invoking origin) and the
+//! per-target concurrency limit.
+//! The concurrency slot is only held for the handshake. An open socket is
+//! idle most of the time, and holding a slot for its whole life would leave
+//! a target with a few open sockets refusing every `hecate://` request.
+
+use base64::Engine;
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::http::Method;
+use tauri::ipc::Channel;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::{HeaderValue, Uri};
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::correlation;
+use crate::plugin_isolation::{self, Caller};
+use crate::plugin_name::PluginName;
+use crate::proxy_error::ProxyError;
+use crate::proxy_limits;
+use crate::proxy_policy;
+use crate::session_identity;
+use crate::socket_proxy;
+use crate::traffic;
+use crate::transport;
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What `ws_open` reports on the connection's channel.
+#[derive(Serialize, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum WsEvent {
+    Text { data: String },
+    /// Binary payload, base64-encoded.
+    Binary { data: String },
+    Close { code: u16, reason: String },
+    Error { error: String },
+}
+
+/// A frame to send with `ws_send`.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum WsOutgoing {
+    Text { data: String },
+    /// Binary payload, base64-encoded.
+    Binary { data: String },
+}
+
+enum Command {
+    Send(Message),
+    Close(Option<CloseFrame>),
+}
+
+#[derive(Serialize)]
+pub struct WsOpened {
+    pub id: String,
+    /// Subprotocol the daemon picked, if any.
+    pub protocol: Option<String>,
+}
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Open connections by id: the sender feeding each connection's task.
+static CONNECTIONS: Mutex<Option<HashMap<String, mpsc::UnboundedSender<Command>>>> = Mutex::new(None);
+
+/// Tauri command: open a WebSocket to a daemon or plugin path, on behalf of
+/// `plugin` if given.
+#[tauri::command]
+pub async fn ws_open(
+    invoke: tauri::ipc::Request<'_>,
+    path: String,
+    protocols: Option<Vec<String>>,
+    plugin: Option<PluginName>,
+    on_event: Channel<WsEvent>,
+) -> Result<WsOpened, String> {
+    let uri: Uri = path.parse().map_err(|e| format!("invalid path {:?}: {}", path, e))?;
+    let (path_only, query) = (uri.path(), uri.query());
+    let route = socket_proxy::resolve_socket_for_path(uri.host(), path_only).map_err(|e| e.to_string())?;
+    if !proxy_policy::check(&route.target, "GET", &route.path) {
+        return Err(ProxyError::Forbidden(format!("GET {}", path_only)).to_string());
+    }
+    let caller = match plugin {
+        Some(name) => Caller::Plugin(name),
+        None => plugin_isolation::caller_of(invoke.headers(), None),
+    };
+    if !plugin_isolation::permits(&caller, &route.target, &Method::GET, &route.path).await {
+        return Err(ProxyError::NotGranted(format!("GET {}", path_only)).to_string());
+    }
+    if !transport::is_available(&route.socket_path) {
+        return Err(format!("socket not found: {}", route.socket_path));
+    }
+    let handshake_permit = proxy_limits::acquire(&route.target).await.map_err(|e| e.to_string())?;
+
+    let target_path = match query {
+        Some(q) => format!("{}?{}", route.path, q),
+        None => route.path.clone(),
+    };
+    let mut request = format!("ws://{}{}", transport::host_header(&route.socket_path), target_path)
+        .into_client_request()
+        .map_err(|e| e.to_string())?;
+    let ids = correlation::Ids::generate();
+    ids.apply(request.headers_mut());
+    session_identity::apply(request.headers_mut());
+    if let Some(protocols) = protocols.filter(|p| !p.is_empty()) {
+        let value = HeaderValue::from_str(&protocols.join(", ")).map_err(|e| e.to_string())?;
+        request.headers_mut().insert("Sec-WebSocket-Protocol", value);
+    }
+
+    let stream = transport::connect(&route.socket_path)
+        .await
+        .map_err(|e| format!("connect {}: {}", route.socket_path, e))?;
+    let (ws, response) = tokio::time::timeout(HANDSHAKE_TIMEOUT, tokio_tungstenite::client_async(request, stream))
+        .await
+        .map_err(|_| "WebSocket handshake timed out".to_string())?
+        .map_err(|e| format!("WebSocket handshake failed: {}", e))?;
+    drop(handshake_permit);
+    let protocol = response
+        .headers()
+        .get("Sec-WebSocket-Protocol")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string());
+
+    let id = format!("ws-{}", NEXT_ID.fetch_add(1, Ordering::Relaxed));
+    let (tx, rx) = mpsc::unbounded_channel();
+    if let Ok(mut conns) = CONNECTIONS.lock() {
+        conns.get_or_insert_with(HashMap::new).insert(id.clone(), tx);
+    }
+    eprintln!(
+        "[ws_tunnel] {} open to {} {} (request {})",
+        id, route.target, target_path, ids.request_id
+    );
+
+    let task_id = id.clone();
+    tauri::async_runtime::spawn(async move {
+        pump(&task_id, ws, rx, &on_event).await;
+        if let Ok(mut conns) = CONNECTIONS.lock() {
+            if let Some(map) = conns.as_mut() {
+                map.remove(&task_id);
+            }
+        }
+        eprintln!("[ws_tunnel] {} closed", task_id);
+    });
+
+    Ok(WsOpened { id, protocol })
+}
+
+/// Tauri command: send a text or binary frame on an open connection.
+#[tauri::command]
+pub fn ws_send(id: String, message: WsOutgoing) -> Result<(), String> {
+    let message = match message {
+        WsOutgoing::Text { data } => Message::text(data),
+        WsOutgoing::Binary { data } => Message::binary(
+            base64::engine::general_purpose::STANDARD
+                .decode(data)
+                .map_err(|e| format!("invalid base64: {}", e))?,
+        ),
+    };
+    command(&id, Command::Send(message))
+}
+
+/// Tauri command: close a connection (default code 1000).
+#[tauri::command]
+pub fn ws_close(id: String, code: Option<u16>, reason: Option<String>) -> Result<(), String> {
+    let frame = CloseFrame {
+        code: CloseCode::from(code.unwrap_or(1000)),
+        reason: reason.unwrap_or_default().into(),
+    };
+    command(&id, Command::Close(Some(frame)))
+}
+
+fn command(id: &str, cmd: Command) -> Result<(), String> {
+    let tx = CONNECTIONS
+        .lock()
+        .ok()
+        .and_then(|conns| conns.as_ref().and_then(|m| m.get(id).cloned()))
+        .ok_or_else(|| format!("no open WebSocket {}", id))?;
+    tx.send(cmd).map_err(|_| format!("WebSocket {} is closing", id))
+}
+
+/// Shuttle frames both ways until either side closes.
+async fn pump(
+    id: &str,
+    ws: tokio_tungstenite::WebSocketStream<transport::Stream>,
+    mut rx: mpsc::UnboundedReceiver<Command>,
+    on_event: &Channel<WsEvent>,
+) {
+    let (mut sink, mut stream) = ws.split();
+    loop {
+        tokio::select! {
+            incoming = stream.next() => {
+                let message = match incoming {
+                    Some(Ok(m)) => m,
+                    Some(Err(e)) => {
+                        let _ = on_event.send(WsEvent::Error { error: e.to_string() });
+                        return;
+                    }
+                    None => {
+                        let _ = on_event.send(WsEvent::Close { code: 1006, reason: String::new() });
+                        return;
+                    }
+                };
+                traffic::record_rx(message.len() as u64);
+                let event = match message {
+                    Message::Text(text) => WsEvent::Text { data: text.to_string() },
+                    Message::Binary(data) => WsEvent::Binary {
+                        data: base64::engine::general_purpose::STANDARD.encode(&data),
+                    },
+                    Message::Close(frame) => {
+                        let (code, reason) = frame
+                            .map(|f| (u16::from(f.code), f.reason.to_string()))
+                            .unwrap_or((1005, String::new()));
+                        // tungstenite echoes the close; flush it before reporting
+                        let _ = sink.flush().await;
+                        let _ = on_event.send(WsEvent::Close { code, reason });
+                        return;
+                    }
+                    // Pings are answered by tungstenite on the next write/flush
+                    Message::Ping(_) | Message::Pong(_) | Message::Frame(_) => {
+                        let _ = sink.flush().await;
+                        continue;
+                    }
+                };
+                if on_event.send(event).is_err() {
+                    eprintln!("[ws_tunnel] {} channel gone, closing", id);
+                    let _ = sink.send(Message::Close(None)).await;
+                    return;
+                }
+            }
+            cmd = rx.recv() => {
+                let result = match cmd {
+                    Some(Command::Send(message)) => {
+                        traffic::record_tx(message.len() as u64);
+                        sink.send(message).await
+                    }
+                    Some(Command::Close(frame)) => sink.send(Message::Close(frame)).await,
+                    // Every sender dropped: nobody can talk to this socket any more
+                    None => {
+                        let _ = sink.send(Message::Close(None)).await;
+                        return;
+                    }
+                };
+                if let Err(e) = result {
+                    let _ = on_event.send(WsEvent::Error { error: e.to_string() });
+                    return;
+                }
+            }
+        }
+    }
+}
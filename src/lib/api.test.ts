@@ -18,7 +18,7 @@ const mockFetch = vi.fn();
 vi.stubGlobal('fetch', mockFetch);
 
 // Now import the module under test
-const { get, post, put, del, patch, ApiError } = await import('./api');
+const { get, post, put, del, patch, upload, ApiError } = await import('./api');
 
 function jsonResponse(body: unknown, status = 200): Response {
 	return new Response(JSON.stringify(body), {
@@ -175,6 +175,34 @@ describe('patch', () => {
 	});
 });
 
+// --- Upload ---
+
+describe('upload', () => {
+	it('sends FormData untouched and lets fetch set the multipart Content-Type', async () => {
+		mockFetch.mockResolvedValueOnce(jsonResponse({ ok: true }));
+		const form = new FormData();
+		form.append('file', new Blob(['hi'], { type: 'text/plain' }), 'hi.txt');
+
+		await upload('/api/avatar', form);
+
+		const [url, opts] = mockFetch.mock.calls[0];
+		expect(url).toBe('hecate://localhost/api/avatar');
+		expect(opts.method).toBe('POST');
+		expect(opts.body).toBe(form);
+		expect(opts.headers['Content-Type']).toBeUndefined();
+	});
+
+	it('labels raw bytes as application/octet-stream', async () => {
+		mockFetch.mockResolvedValueOnce(jsonResponse({ ok: true }));
+
+		await upload('/plugin/docs/api/files', new ArrayBuffer(4), 'PUT');
+
+		const [, opts] = mockFetch.mock.calls[0];
+		expect(opts.method).toBe('PUT');
+		expect(opts.headers['Content-Type']).toBe('application/octet-stream');
+	});
+});
+
 // --- Error handling ---
 
 describe('error handling', () => {
@@ -80,3 +80,21 @@ export async function patch<T>(path: string, body: unknown): Promise<T> {
 	});
 	return handleResponse<T>(resp);
 }
+
+/**
+ * Upload a file or form without JSON-encoding it. FormData goes out as
+ * multipart/form-data (the browser sets the boundary), a Blob with its own
+ * type, and raw bytes as application/octet-stream.
+ */
+export async function upload<T>(
+	path: string,
+	body: FormData | Blob | ArrayBuffer,
+	method: 'POST' | 'PUT' = 'POST'
+): Promise<T> {
+	const headers: Record<string, string> = { ...authHeaders() };
+	if (body instanceof ArrayBuffer || (body instanceof Blob && !body.type)) {
+		headers['Content-Type'] = 'application/octet-stream';
+	}
+	const resp = await fetch(`${BASE}${path}`, { method, headers, body });
+	return handleResponse<T>(resp);
+}
@@ -0,0 +1,121 @@
+import { describe, it, expect, vi } from 'vitest';
+import type { PluginApi, PluginSocketEvent, PluginStreamEvent } from '$lib/stores/plugins';
+
+vi.mock('@tauri-apps/api/core', () => ({ invoke: vi.fn(), Channel: class {} }));
+vi.mock('@tauri-apps/api/event', () => ({ listen: vi.fn() }));
+vi.mock('$lib/api', () => ({}));
+
+const { createBridge } = await import('./plugin-bridge');
+const { createFrameApi } = await import('./plugin-frame/api.js');
+
+function fakeSocket() {
+	return {
+		protocol: 'v1',
+		send: vi.fn(async () => {}),
+		close: vi.fn(async () => {})
+	};
+}
+
+function fakeApi() {
+	const socket = fakeSocket();
+	let socketEvents: (event: PluginSocketEvent) => void = () => {};
+	const api = {
+		get: vi.fn(),
+		post: vi.fn(),
+		put: vi.fn(),
+		patch: vi.fn(),
+		del: vi.fn(),
+		openSocket: vi.fn(async (_path: string, onEvent: (event: PluginSocketEvent) => void) => {
+			socketEvents = onEvent;
+			return socket;
+		}),
+		stream: vi.fn(async (_path: string, onEvent: (event: PluginStreamEvent) => void) => {
+			onEvent({ type: 'event', data: { n: 1 } });
+			onEvent({ type: 'done' });
+		})
+	} satisfies PluginApi;
+	return { api, socket, emit: (event: PluginSocketEvent) => socketEvents(event) };
+}
+
+// Wire a frame api to a shell bridge the way postMessage would: async, and
+// by structured clone.
+function connect(api: PluginApi) {
+	const replies: unknown[] = [];
+	let toFrame: (message: unknown) => void = () => {};
+	const bridge = createBridge(api, (reply) => {
+		replies.push(reply);
+		queueMicrotask(() => toFrame(structuredClone(reply)));
+	});
+	const port = {
+		post: (message: object) => queueMicrotask(() => bridge.handle(structuredClone(message))),
+		listen: (handler: (message: unknown) => void) => {
+			toFrame = handler;
+		}
+	};
+	return { frame: createFrameApi(vi.fn(), port), bridge, replies };
+}
+
+const settle = () => new Promise((resolve) => setTimeout(resolve, 0));
+
+describe('plugin frame bridge', () => {
+	it('opens, uses and closes a socket through the shell', async () => {
+		const { api, socket, emit } = fakeApi();
+		const { frame } = connect(api);
+		const events: unknown[] = [];
+
+		const opened = await frame.openSocket('/ws', (e) => events.push(e), ['v1']);
+		await opened.send({ type: 'text', data: 'hello' });
+		emit({ type: 'text', data: 'back' });
+		await settle();
+		await opened.close(1000, 'bye');
+
+		expect(api.openSocket).toHaveBeenCalledWith('/ws', expect.any(Function), ['v1']);
+		expect(opened.protocol).toBe('v1');
+		expect(socket.send).toHaveBeenCalledWith({ type: 'text', data: 'hello' });
+		expect(socket.close).toHaveBeenCalledWith(1000, 'bye');
+		expect(events).toEqual([{ type: 'text', data: 'back' }]);
+	});
+
+	it('streams events until done', async () => {
+		const { api } = fakeApi();
+		const { frame } = connect(api);
+		const events: unknown[] = [];
+
+		await frame.stream('/events', (e) => events.push(e));
+		await settle();
+
+		expect(api.stream).toHaveBeenCalledWith('/events', expect.any(Function));
+		expect(events).toEqual([{ type: 'event', data: { n: 1 } }, { type: 'done' }]);
+	});
+
+	it('refuses sockets the frame did not open and malformed messages', async () => {
+		const { api, socket } = fakeApi();
+		const { bridge, replies } = connect(api);
+
+		bridge.handle({ bridge: 'hecate', id: 1, kind: 'ws-send', socket: 42, message: { type: 'text', data: 'x' } });
+		bridge.handle({ bridge: 'other', id: 2, kind: 'ws-open', path: '/ws' });
+		bridge.handle({ bridge: 'hecate', id: 'three', kind: 'ws-open', path: '/ws' });
+		await settle();
+
+		expect(socket.send).not.toHaveBeenCalled();
+		expect(api.openSocket).not.toHaveBeenCalled();
+		expect(replies).toEqual([
+			{ bridge: 'hecate', id: 1, kind: 'failure', error: 'no open socket 42' }
+		]);
+	});
+
+	it('closes open sockets when the frame goes away', async () => {
+		const { api, socket } = fakeApi();
+		const { frame, bridge, replies } = connect(api);
+
+		await frame.openSocket('/ws', () => {});
+		const before = replies.length;
+		bridge.close();
+		bridge.handle({ bridge: 'hecate', id: 9, kind: 'stream', path: '/events' });
+		await settle();
+
+		expect(socket.close).toHaveBeenCalledWith(1001, 'frame closed');
+		expect(api.stream).not.toHaveBeenCalled();
+		expect(replies.length).toBe(before);
+	});
+});
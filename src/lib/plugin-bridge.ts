@@ -0,0 +1,132 @@
+// The shell's end of the plugin frame bridge. Plugin frames have no Tauri
+// IPC; their `api.openSocket` and `api.stream` (src/lib/plugin-frame/api.js)
+// post requests here instead, and they run through the shell's PluginApi
+// for the frame's own plugin. A frame can only name paths, never a plugin,
+// and only reach the sockets it opened itself.
+
+import {
+	createPluginApi,
+	pluginFrameOrigin,
+	type PluginApi,
+	type PluginSocket,
+	type PluginSocketMessage
+} from '$lib/stores/plugins';
+
+/** A request from a frame. `id` is the frame's own, echoed on replies. */
+export type BridgeRequest = { bridge: 'hecate'; id: number } & (
+	| { kind: 'ws-open'; path: string; protocols?: string[] }
+	| { kind: 'ws-send'; socket: number; message: PluginSocketMessage }
+	| { kind: 'ws-close'; socket: number; code?: number; reason?: string }
+	| { kind: 'stream'; path: string }
+);
+
+/** A reply to a frame: a request's outcome, or an event for its listener. */
+export type BridgeReply = { bridge: 'hecate'; id: number } & (
+	| { kind: 'result'; value: unknown }
+	| { kind: 'failure'; error: string }
+	| { kind: 'event'; event: unknown }
+);
+
+type Reply = (reply: BridgeReply) => void;
+
+function isRequest(message: unknown): message is BridgeRequest {
+	const m = message as Partial<BridgeRequest> | null;
+	return m?.bridge === 'hecate' && typeof m.id === 'number' && typeof m.kind === 'string';
+}
+
+/**
+ * Serve one frame's requests with `api`. Returns the message handler and a
+ * `close` that shuts every socket the frame left open.
+ */
+export function createBridge(api: PluginApi, reply: Reply) {
+	const sockets = new Map<number, PluginSocket>();
+	let closed = false;
+	const send: Reply = (message) => {
+		if (!closed) reply(message);
+	};
+
+	async function run(request: BridgeRequest): Promise<unknown> {
+		const event = (event: unknown) => send({ bridge: 'hecate', id: request.id, kind: 'event', event });
+		switch (request.kind) {
+			case 'ws-open': {
+				const socket = await api.openSocket(
+					request.path,
+					(e) => {
+						if (e.type === 'close' || e.type === 'error') sockets.delete(request.id);
+						event(e);
+					},
+					request.protocols
+				);
+				if (closed) {
+					await socket.close(1001, 'frame closed');
+					throw new Error('frame closed');
+				}
+				sockets.set(request.id, socket);
+				return { socket: request.id, protocol: socket.protocol };
+			}
+			case 'ws-send':
+				return socketFor(request.socket).send(request.message);
+			case 'ws-close':
+				return socketFor(request.socket).close(request.code, request.reason);
+			case 'stream':
+				return api.stream(request.path, event);
+		}
+	}
+
+	function socketFor(id: number): PluginSocket {
+		const socket = sockets.get(id);
+		if (!socket) throw new Error(`no open socket ${id}`);
+		return socket;
+	}
+
+	return {
+		handle(message: unknown): void {
+			if (closed || !isRequest(message)) return;
+			const { id } = message;
+			run(message).then(
+				(value) => send({ bridge: 'hecate', id, kind: 'result', value: value ?? null }),
+				(e) => send({ bridge: 'hecate', id, kind: 'failure', error: e instanceof Error ? e.message : String(e) })
+			);
+		},
+		close(): void {
+			closed = true;
+			for (const socket of sockets.values()) {
+				socket.close(1001, 'frame closed').catch(() => {});
+			}
+			sockets.clear();
+		}
+	};
+}
+
+/**
+ * Svelte action for a plugin's iframe: serve its bridge requests as
+ * `pluginName`, taking messages only from that frame on that plugin's origin.
+ */
+export function pluginBridge(frame: HTMLIFrameElement, pluginName: string) {
+	let detach = attach(frame, pluginName);
+	return {
+		update(next: string) {
+			detach();
+			detach = attach(frame, next);
+		},
+		destroy() {
+			detach();
+		}
+	};
+}
+
+function attach(frame: HTMLIFrameElement, pluginName: string): () => void {
+	const origin = pluginFrameOrigin(pluginName);
+	const bridge = createBridge(createPluginApi(pluginName), (reply) =>
+		frame.contentWindow?.postMessage(reply, origin)
+	);
+	const onMessage = (event: MessageEvent) => {
+		if (event.source !== frame.contentWindow || event.origin !== origin) return;
+		bridge.handle(event.data);
+	};
+	window.addEventListener('message', onMessage);
+	return () => {
+		window.removeEventListener('message', onMessage);
+		bridge.close();
+	};
+}
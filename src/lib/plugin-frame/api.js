@@ -0,0 +1,225 @@
+// The `api` object handed to a plugin's custom element inside its frame.
+// Same methods as the shell's `PluginApi` (src/lib/stores/plugins.ts);
+// root-relative paths reach the plugin's own daemon, since the frame is
+// served from hecate://{name}.plugin. Served as /_hecate/api.js.
+//
+// The frame has no Tauri IPC, so sockets and streams are asked of the shell
+// over postMessage (see src/lib/plugin-bridge.ts for the other end).
+
+/**
+ * @typedef {{ post: (message: object) => void; listen: (handler: (message: any) => void) => void }} BridgePort
+ */
+
+/** @returns {BridgePort} */
+function parentPort() {
+	return {
+		// The shell's origin differs between dev and release builds; replies
+		// come back addressed to this frame's origin only
+		post: (message) => window.parent.postMessage(message, '*'),
+		listen: (handler) =>
+			window.addEventListener('message', (event) => {
+				if (event.source === window.parent) handler(event.data);
+			})
+	};
+}
+
+/**
+ * Requests to the shell's bridge, matched to replies by id.
+ * @param {BridgePort} port
+ */
+function createBridgeClient(port) {
+	let nextId = 0;
+	/** @type {Map<number, { resolve: (value: any) => void; reject: (error: Error) => void }>} */
+	const pending = new Map();
+	/** @type {Map<number, (event: any) => void>} */
+	const listeners = new Map();
+
+	port.listen((message) => {
+		if (message?.bridge !== 'hecate' || typeof message.id !== 'number') return;
+		if (message.kind === 'event') {
+			listeners.get(message.id)?.(message.event);
+			if (['close', 'error', 'done'].includes(message.event?.type)) listeners.delete(message.id);
+			return;
+		}
+		const request = pending.get(message.id);
+		if (!request) return;
+		pending.delete(message.id);
+		if (message.kind === 'result') request.resolve(message.value);
+		else request.reject(new Error(message.error));
+	});
+
+	/**
+	 * @param {string} kind
+	 * @param {object} fields
+	 * @param {(event: any) => void} [onEvent]
+	 * @returns {Promise<any>}
+	 */
+	function request(kind, fields, onEvent) {
+		const id = ++nextId;
+		if (onEvent) listeners.set(id, onEvent);
+		return new Promise((resolve, reject) => {
+			pending.set(id, { resolve, reject });
+			port.post({ bridge: 'hecate', kind, id, ...fields });
+		}).catch((e) => {
+			listeners.delete(id);
+			throw e;
+		});
+	}
+
+	return {
+		/**
+		 * @param {string} path
+		 * @param {(event: any) => void} onEvent
+		 * @param {string[]} [protocols]
+		 */
+		async openSocket(path, onEvent, protocols) {
+			/** @type {{ socket: number; protocol: string | null }} */
+			const opened = await request('ws-open', { path, protocols }, onEvent);
+			return {
+				protocol: opened.protocol,
+				/** @param {{ type: 'text' | 'binary'; data: string }} message */
+				send: (message) => request('ws-send', { socket: opened.socket, message }),
+				/**
+				 * @param {number} [code]
+				 * @param {string} [reason]
+				 */
+				close: (code, reason) => request('ws-close', { socket: opened.socket, code, reason })
+			};
+		},
+		/**
+		 * @param {string} path
+		 * @param {(event: any) => void} onEvent
+		 * @returns {Promise<void>}
+		 */
+		stream(path, onEvent) {
+			return request('stream', { path }, onEvent);
+		}
+	};
+}
+
+export class PluginApiError extends Error {
+	/**
+	 * @param {number} status
+	 * @param {string | null} code
+	 * @param {string} message
+	 */
+	constructor(status, code, message) {
+		super(message);
+		this.name = 'ApiError';
+		this.status = status;
+		this.code = code;
+	}
+}
+
+/**
+ * @param {string} text
+ * @param {string} fallback
+ * @returns {{ code: string | null; message: string }}
+ */
+function parseErrorBody(text, fallback) {
+	try {
+		const json = JSON.parse(text);
+		if (json.error) {
+			const code = String(json.error);
+			return { code, message: code.replace(/_/g, ' ') };
+		}
+	} catch {
+		// Not JSON — use as-is
+	}
+	return { code: null, message: text || fallback };
+}
+
+/**
+ * @param {(input: string, init: RequestInit) => Promise<Response>} [fetchImpl]
+ * @param {BridgePort} [port] Where to reach the shell; the parent window by default.
+ */
+export function createFrameApi(fetchImpl = (input, init) => fetch(input, init), port) {
+	/** @type {ReturnType<typeof createBridgeClient> | null} */
+	let bridge = null;
+	// Connected on first use, so the HTTP methods work without a parent
+	const shell = () => (bridge ??= createBridgeClient(port ?? parentPort()));
+
+	/**
+	 * @param {string} method
+	 * @param {string} path
+	 * @param {unknown} [body]
+	 * @returns {Promise<any>}
+	 */
+	async function call(method, path, body) {
+		/** @type {RequestInit} */
+		const init = { method };
+		if (body !== undefined) {
+			init.headers = { 'Content-Type': 'application/json' };
+			init.body = JSON.stringify(body);
+		}
+		const resp = await fetchImpl(path, init);
+		if (!resp.ok) {
+			const text = await resp.text().catch(() => resp.statusText);
+			const { code, message } = parseErrorBody(text, resp.statusText);
+			throw new PluginApiError(resp.status, code, message);
+		}
+		return resp.json();
+	}
+
+	return {
+		/**
+		 * @template T
+		 * @param {string} path
+		 * @returns {Promise<T>}
+		 */
+		get(path) {
+			return call('GET', path);
+		},
+		/**
+		 * @template T
+		 * @param {string} path
+		 * @param {unknown} body
+		 * @returns {Promise<T>}
+		 */
+		post(path, body) {
+			return call('POST', path, body);
+		},
+		/**
+		 * @template T
+		 * @param {string} path
+		 * @param {unknown} body
+		 * @returns {Promise<T>}
+		 */
+		put(path, body) {
+			return call('PUT', path, body);
+		},
+		/**
+		 * @template T
+		 * @param {string} path
+		 * @param {unknown} body
+		 * @returns {Promise<T>}
+		 */
+		patch(path, body) {
+			return call('PATCH', path, body);
+		},
+		/**
+		 * @template T
+		 * @param {string} path
+		 * @returns {Promise<T>}
+		 */
+		del(path) {
+			return call('DELETE', path);
+		},
+		/**
+		 * @param {string} path
+		 * @param {(event: any) => void} onEvent
+		 * @param {string[]} [protocols]
+		 */
+		openSocket(path, onEvent, protocols) {
+			return shell().openSocket(path, onEvent, protocols);
+		},
+		/**
+		 * @param {string} path
+		 * @param {(event: any) => void} onEvent
+		 * @returns {Promise<void>}
+		 */
+		stream(path, onEvent) {
+			return shell().stream(path, onEvent);
+		}
+	};
+}
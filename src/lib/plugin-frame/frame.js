@@ -0,0 +1,12 @@
+// Mounts this plugin's custom element with an api bound to its own host.
+// Served as /_hecate/frame.js, next to /_hecate/api.js.
+import { createFrameApi } from './api.js';
+
+const api = createFrameApi();
+/** @type {{ tag: string }} */
+const manifest = await api.get('/manifest');
+const componentUrl = '/ui/component.js';
+await import(/* @vite-ignore */ componentUrl);
+const el = document.createElement(manifest.tag);
+Object.assign(el, { api });
+document.body.appendChild(el);
@@ -1,6 +1,7 @@
 import { writable, derived } from 'svelte/store';
 import { invoke } from '@tauri-apps/api/core';
-import type { DaemonHealth, ConnectionStatus } from '../types.js';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import type { RecentDaemonEvents, DaemonHealth, ConnectionStatus, DaemonStreamState } from '../types.js';
 
 export const health = writable<DaemonHealth | null>(null);
 export const connectionStatus = writable<ConnectionStatus>('connecting');
@@ -8,6 +9,8 @@ export const lastError = writable<string | null>(null);
 export const unavailableSince = writable<number | null>(null);
 /** Diagnostic: last error detail visible on the overlay */
 export const debugError = writable<string>('');
+/** State of the daemon event stream (SSE) kept by the Rust side */
+export const streamState = writable<DaemonStreamState>({ state: 'stopped' });
 
 export const isConnected = derived(connectionStatus, ($s) => $s === 'connected');
 export const isHealthy = derived(health, ($h) => $h?.status === 'healthy' && $h?.ready === true);
@@ -87,3 +90,34 @@ export function stopPolling(): void {
 		healthTimer = null;
 	}
 }
+
+let streamStateUnlisten: UnlistenFn | null = null;
+
+export async function startStreamStateWatcher(): Promise<void> {
+	if (streamStateUnlisten) return;
+	streamStateUnlisten = await listen<DaemonStreamState>('daemon-stream-state', (event) => {
+		streamState.set(event.payload);
+	});
+	try {
+		streamState.set(await invoke<DaemonStreamState>('get_daemon_stream_state'));
+	} catch {
+		// Tauri command not available yet — the next event fills it in
+	}
+}
+
+export function stopStreamStateWatcher(): void {
+	if (streamStateUnlisten) {
+		streamStateUnlisten();
+		streamStateUnlisten = null;
+	}
+}
+
+/**
+ * Daemon events forwarded before the caller was listening, oldest first:
+ * the latest of each type, or (with `since`) the first `limit` events after
+ * that sequence number, with `truncated` set when more follow and `gap` when
+ * some were already dropped. Lets a freshly mounted view rebuild its state.
+ */
+export async function recentDaemonEvents(since?: number, limit?: number): Promise<RecentDaemonEvents> {
+	return invoke<RecentDaemonEvents>('get_recent_daemon_events', { since, limit });
+}
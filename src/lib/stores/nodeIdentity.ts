@@ -26,9 +26,17 @@ let identityUnlisten: UnlistenFn | null = null;
 
 export async function startIdentityWatcher(): Promise<void> {
 	if (identityUnlisten) return;
-	identityUnlisten = await listen('daemon-identity-changed', () => {
+	const unlistenChanged = await listen('daemon-identity-changed', () => {
 		fetchNodeIdentity();
 	});
+	// Events were missed while the stream was down; refetch to catch up
+	const unlistenGap = await listen('daemon-stream-gap', () => {
+		fetchNodeIdentity();
+	});
+	identityUnlisten = () => {
+		unlistenChanged();
+		unlistenGap();
+	};
 }
 
 export function stopIdentityWatcher(): void {
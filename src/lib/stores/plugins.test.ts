@@ -0,0 +1,128 @@
+import { describe, it, expect, vi, beforeEach } from 'vitest';
+import type { PluginApi } from './plugins';
+
+const invoke = vi.fn();
+vi.mock('@tauri-apps/api/core', () => ({
+	invoke,
+	Channel: class {
+		onmessage: (message: unknown) => void = () => {};
+	}
+}));
+vi.mock('@tauri-apps/api/event', () => ({ listen: vi.fn(async () => () => {}) }));
+
+const shellApi = {
+	get: vi.fn(),
+	post: vi.fn(),
+	put: vi.fn(),
+	patch: vi.fn(),
+	del: vi.fn()
+};
+vi.mock('$lib/api', () => shellApi);
+
+const { createPluginApi } = await import('./plugins');
+const { createFrameApi, PluginApiError } = await import('../plugin-frame/api.js');
+
+const mockFetch = vi.fn();
+
+function jsonResponse(body: unknown, status = 200): Response {
+	return new Response(JSON.stringify(body), {
+		status,
+		statusText: status === 200 ? 'OK' : 'Error',
+		headers: { 'Content-Type': 'application/json' }
+	});
+}
+
+beforeEach(() => {
+	mockFetch.mockReset();
+	invoke.mockReset();
+	Object.values(shellApi).forEach((fn) => fn.mockReset());
+});
+
+// --- Same surface in the shell and in the plugin frame ---
+
+describe('plugin api', () => {
+	it('frame api has exactly the PluginApi methods', () => {
+		const frame: PluginApi = createFrameApi(mockFetch);
+		const shell: PluginApi = createPluginApi('demo');
+
+		expect(Object.keys(frame).sort()).toEqual(Object.keys(shell).sort());
+		for (const method of Object.keys(shell)) {
+			expect(typeof frame[method as keyof PluginApi]).toBe('function');
+		}
+	});
+
+	it('shell api prefixes the plugin namespace', async () => {
+		const api = createPluginApi('demo');
+
+		await api.get('/items');
+		await api.put('/items/1', { a: 1 });
+		await api.patch('/items/1', { a: 2 });
+
+		expect(shellApi.get).toHaveBeenCalledWith('/plugin/demo/items');
+		expect(shellApi.put).toHaveBeenCalledWith('/plugin/demo/items/1', { a: 1 });
+		expect(shellApi.patch).toHaveBeenCalledWith('/plugin/demo/items/1', { a: 2 });
+	});
+
+	it('shell api opens sockets and streams as the plugin', async () => {
+		invoke.mockResolvedValueOnce({ id: 'ws-7', protocol: null });
+		const api = createPluginApi('demo');
+
+		const socket = await api.openSocket('/ws', () => {}, ['v1']);
+		await socket.send({ type: 'text', data: 'hi' });
+		await api.stream('/events', () => {});
+
+		expect(invoke.mock.calls[0][0]).toBe('ws_open');
+		expect(invoke.mock.calls[0][1]).toMatchObject({
+			path: '/plugin/demo/ws',
+			protocols: ['v1'],
+			plugin: 'demo'
+		});
+		expect(invoke.mock.calls[1]).toEqual([
+			'ws_send',
+			{ id: 'ws-7', message: { type: 'text', data: 'hi' } }
+		]);
+		expect(invoke.mock.calls[2][0]).toBe('plugin_sse_stream');
+		expect(invoke.mock.calls[2][1]).toMatchObject({ plugin: 'demo', path: '/events' });
+	});
+});
+
+// --- Frame api requests ---
+
+describe('frame api', () => {
+	it.each([
+		['get', 'GET', undefined],
+		['del', 'DELETE', undefined],
+		['post', 'POST', { a: 1 }],
+		['put', 'PUT', { a: 2 }],
+		['patch', 'PATCH', { a: 3 }]
+	] as const)('%s sends %s to the root-relative path', async (name, method, body) => {
+		mockFetch.mockResolvedValueOnce(jsonResponse({ ok: true }));
+		const api = createFrameApi(mockFetch);
+
+		const result =
+			body === undefined
+				? await (api[name] as PluginApi['get'])('/items')
+				: await (api[name] as PluginApi['post'])('/items', body);
+
+		expect(result).toEqual({ ok: true });
+		const [path, init] = mockFetch.mock.calls[0];
+		expect(path).toBe('/items');
+		expect(init.method).toBe(method);
+		if (body === undefined) {
+			expect(init.body).toBeUndefined();
+		} else {
+			expect(init.headers['Content-Type']).toBe('application/json');
+			expect(init.body).toBe(JSON.stringify(body));
+		}
+	});
+
+	it('throws ApiError with status and code', async () => {
+		mockFetch.mockResolvedValueOnce(jsonResponse({ ok: false, error: 'not_granted' }, 403));
+		const api = createFrameApi(mockFetch);
+
+		const err = await api.get('/other').catch((e: unknown) => e);
+
+		expect(err).toBeInstanceOf(PluginApiError);
+		expect(err).toMatchObject({ name: 'ApiError', status: 403, code: 'not_granted', message: 'not granted' });
+	});
+});
@@ -1,8 +1,14 @@
-// Plugin discovery, manifest fetching, and dynamic custom element loading
+// Plugin discovery and manifest fetching; plugin UIs run in a frame on their own origin
 import { writable, derived } from 'svelte/store';
-import { invoke } from '@tauri-apps/api/core';
+import { invoke, Channel } from '@tauri-apps/api/core';
 import { listen, type UnlistenFn } from '@tauri-apps/api/event';
-import { get as apiGet, post as apiPost, del as apiDel } from '$lib/api';
+import {
+	get as apiGet,
+	post as apiPost,
+	put as apiPut,
+	patch as apiPatch,
+	del as apiDel
+} from '$lib/api';
 
 interface PluginDiscovery {
 	name: string;
@@ -26,22 +32,136 @@ export interface LoadedPlugin {
 	manifest: PluginManifest;
 	tag: string;
 	api: PluginApi;
+	frameUrl: string;
 }
 
+/** What an open plugin WebSocket reports (binary data is base64). */
+export type PluginSocketEvent =
+	| { type: 'text'; data: string }
+	| { type: 'binary'; data: string }
+	| { type: 'close'; code: number; reason: string }
+	| { type: 'error'; error: string };
+
+/** A frame to send on a plugin WebSocket (binary data is base64). */
+export type PluginSocketMessage = { type: 'text'; data: string } | { type: 'binary'; data: string };
+
+export interface PluginSocket {
+	/** Subprotocol the daemon picked, if any. */
+	protocol: string | null;
+	send: (message: PluginSocketMessage) => Promise<void>;
+	close: (code?: number, reason?: string) => Promise<void>;
+}
+
+/** What a plugin SSE stream reports: each event's data, then done or error. */
+export type PluginStreamEvent =
+	| { type: 'event'; data: unknown }
+	| { type: 'done' }
+	| { type: 'error'; error: string };
+
+// The frame runtime (src/lib/plugin-frame/api.js) gives plugin components
+// the same methods; plugins.test.ts keeps the two in step.
 export interface PluginApi {
 	get: <T>(path: string) => Promise<T>;
 	post: <T>(path: string, body: unknown) => Promise<T>;
+	put: <T>(path: string, body: unknown) => Promise<T>;
+	patch: <T>(path: string, body: unknown) => Promise<T>;
 	del: <T>(path: string) => Promise<T>;
+	/** Open a WebSocket to a path on the plugin's daemon. */
+	openSocket: (
+		path: string,
+		onEvent: (event: PluginSocketEvent) => void,
+		protocols?: string[]
+	) => Promise<PluginSocket>;
+	/** Follow an SSE endpoint on the plugin's daemon; resolves once started, events end with done or error. */
+	stream: (path: string, onEvent: (event: PluginStreamEvent) => void) => Promise<void>;
+}
+
+let nextStreamId = 0;
+
+// The origin plugin UIs run on, so the proxy attributes their requests to
+// them (and only them).
+export function pluginFrameOrigin(pluginName: string): string {
+	return `hecate://${pluginName}.plugin`;
+}
+
+// The document that mounts the plugin's custom element on that origin.
+function pluginFrameUrl(pluginName: string): string {
+	return `${pluginFrameOrigin(pluginName)}/_hecate/frame`;
 }
 
-function createPluginApi(pluginName: string): PluginApi {
+export function createPluginApi(pluginName: string): PluginApi {
 	return {
 		get: <T>(path: string) => apiGet<T>(`/plugin/${pluginName}${path}`),
 		post: <T>(path: string, body: unknown) => apiPost<T>(`/plugin/${pluginName}${path}`, body),
-		del: <T>(path: string) => apiDel<T>(`/plugin/${pluginName}${path}`)
+		put: <T>(path: string, body: unknown) => apiPut<T>(`/plugin/${pluginName}${path}`, body),
+		patch: <T>(path: string, body: unknown) => apiPatch<T>(`/plugin/${pluginName}${path}`, body),
+		del: <T>(path: string) => apiDel<T>(`/plugin/${pluginName}${path}`),
+		openSocket: (path, onEvent, protocols) => openPluginSocket(pluginName, path, onEvent, protocols),
+		stream: (path, onEvent) => streamPlugin(pluginName, path, onEvent)
 	};
 }
 
+// Sockets and streams are opened as the plugin (`plugin`), so isolation
+// applies just as for its frame's own requests.
+async function openPluginSocket(
+	pluginName: string,
+	path: string,
+	onEvent: (event: PluginSocketEvent) => void,
+	protocols?: string[]
+): Promise<PluginSocket> {
+	const channel = new Channel<PluginSocketEvent>();
+	channel.onmessage = onEvent;
+	const opened = await invoke<{ id: string; protocol: string | null }>('ws_open', {
+		path: `/plugin/${pluginName}${path}`,
+		protocols,
+		plugin: pluginName,
+		onEvent: channel
+	});
+	return {
+		protocol: opened.protocol,
+		send: (message) => invoke('ws_send', { id: opened.id, message }),
+		close: (code, reason) => invoke('ws_close', { id: opened.id, code, reason })
+	};
+}
+
+async function streamPlugin(
+	pluginName: string,
+	path: string,
+	onEvent: (event: PluginStreamEvent) => void
+): Promise<void> {
+	const streamId = `${pluginName}-${++nextStreamId}`;
+	const eventName = `plugin-stream/${streamId}`;
+	const doneEvent = `${eventName}/done`;
+	const errorEvent = `${eventName}/error`;
+	const unlisteners: UnlistenFn[] = [];
+	const stop = () => unlisteners.splice(0).forEach((unlisten) => unlisten());
+
+	unlisteners.push(
+		await listen<unknown>(eventName, (e) => onEvent({ type: 'event', data: e.payload })),
+		await listen(doneEvent, () => {
+			stop();
+			onEvent({ type: 'done' });
+		}),
+		await listen<{ error: string }>(errorEvent, (e) => {
+			stop();
+			onEvent({ type: 'error', error: e.payload.error });
+		})
+	);
+	try {
+		await invoke('plugin_sse_stream', {
+			streamId,
+			plugin: pluginName,
+			path,
+			eventName,
+			doneEvent,
+			errorEvent
+		});
+	} catch (e) {
+		stop();
+		throw e;
+	}
+}
+
 export const plugins = writable<Map<string, LoadedPlugin>>(new Map());
 export const pluginLoadErrors = writable<Map<string, string>>(new Map());
 export const isDiscovering = writable(false);
@@ -65,11 +185,12 @@ export async function discoverPlugins(): Promise<void> {
 				const api = createPluginApi(plugin.name);
 				const manifest = await api.get<PluginManifest>('/manifest');
 
-				const loaded = await loadPluginElement(plugin.name, manifest.tag);
-
-				if (loaded) {
-					currentPlugins.set(plugin.name, { manifest, tag: manifest.tag, api });
-				}
+				currentPlugins.set(plugin.name, {
+					manifest,
+					tag: manifest.tag,
+					api,
+					frameUrl: pluginFrameUrl(plugin.name)
+				});
 			} catch (e) {
 				errors.set(plugin.name, e instanceof Error ? e.message : String(e));
 			}
@@ -89,15 +210,11 @@ async function loadSinglePlugin(name: string): Promise<void> {
 		const api = createPluginApi(name);
 		const manifest = await api.get<PluginManifest>('/manifest');
 
-		const loaded = await loadPluginElement(name, manifest.tag);
-
-		if (loaded) {
-			plugins.update((current) => {
-				const next = new Map(current);
-				next.set(name, { manifest, tag: manifest.tag, api });
-				return next;
-			});
-		}
+		plugins.update((current) => {
+			const next = new Map(current);
+			next.set(name, { manifest, tag: manifest.tag, api, frameUrl: pluginFrameUrl(name) });
+			return next;
+		});
 	} catch (e) {
 		console.error(`[plugins] Failed to load plugin ${name}:`, e);
 		pluginLoadErrors.update((current) => {
@@ -108,27 +225,6 @@ async function loadSinglePlugin(name: string): Promise<void> {
 	}
 }
 
-async function loadPluginElement(pluginName: string, tag: string): Promise<boolean> {
-	// Guard: don't re-register if already defined
-	if (customElements.get(tag)) return true;
-
-	const url = `hecate://localhost/plugin/${pluginName}/ui/component.js`;
-	try {
-		const resp = await fetch(url);
-		if (!resp.ok) return false;
-		const text = await resp.text();
-		const blob = new Blob([text], { type: 'application/javascript' });
-		const blobUrl = URL.createObjectURL(blob);
-		await import(/* @vite-ignore */ blobUrl);
-		URL.revokeObjectURL(blobUrl);
-		// Importing the module auto-registers the custom element
-		return customElements.get(tag) !== undefined;
-	} catch (e) {
-		console.error(`[plugins] Failed to load element for ${pluginName}:`, e);
-		return false;
-	}
-}
-
 async function handlePluginEvent(event: PluginChangedEvent): Promise<void> {
 	switch (event.event_type) {
 		case 'rescan':
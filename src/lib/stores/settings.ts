@@ -94,9 +94,17 @@ let settingsUnlisten: UnlistenFn | null = null;
 
 export async function startSettingsWatcher(): Promise<void> {
 	if (settingsUnlisten) return;
-	settingsUnlisten = await listen('daemon-settings-changed', () => {
+	const unlistenChanged = await listen('daemon-settings-changed', () => {
 		fetchSettings();
 	});
+	// Events were missed while the stream was down; refetch to catch up
+	const unlistenGap = await listen('daemon-stream-gap', () => {
+		fetchSettings();
+	});
+	settingsUnlisten = () => {
+		unlistenChanged();
+		unlistenGap();
+	};
 }
 
 export function stopSettingsWatcher(): void {
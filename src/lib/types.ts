@@ -12,6 +12,44 @@ export interface DaemonHealth {
 	identity?: 'initialized' | 'not_initialized';
 }
 
+// --- Daemon events ---
+
+/** Payload of `daemon-event`: a daemon SSE event with no mapped Tauri event name. */
+export interface DaemonEvent<T = unknown> {
+	type: string;
+	data: T;
+	/** Position in the recent-event buffer (see `BufferedDaemonEvent`). */
+	seq: number;
+}
+
+/** A recently forwarded daemon event, as kept by the shell for late subscribers. */
+export interface BufferedDaemonEvent<T = unknown> {
+	seq: number;
+	/** The daemon's SSE event type. */
+	type: string;
+	/** The Tauri event it was emitted as. */
+	event: string;
+	data: T;
+	id: string | null;
+	received_at_ms: number;
+}
+
+/** Result of `get_recent_daemon_events`. */
+export interface RecentDaemonEvents {
+	events: BufferedDaemonEvent[];
+	/** Some events after `since` are no longer buffered; refetch state from the daemon. */
+	gap: boolean;
+	/** More events follow; ask again with the last returned `seq`. */
+	truncated: boolean;
+}
+
+/** Payload of `daemon-stream-state` and `get_daemon_stream_state`. */
+export type DaemonStreamState =
+	| { state: 'connecting'; attempt: number }
+	| { state: 'connected' }
+	| { state: 'backoff'; attempt: number; retry_in_ms: number; error: string | null }
+	| { state: 'stopped' };
+
 // --- UI State ---
 
 export type ConnectionStatus = 'connected' | 'connecting' | 'disconnected' | 'error';